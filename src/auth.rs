@@ -0,0 +1,457 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use md5::Md5;
+use reqwest::header::{HeaderMap, HeaderValue, WWW_AUTHENTICATE};
+use reqwest::Method;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::error::Error;
+
+/// A user name and password. The password never appears in `Debug`
+/// output.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    user: String,
+    password: String,
+}
+
+impl Credentials {
+    pub fn new(user: impl Into<String>, password: impl Into<String>) -> Self {
+        Credentials {
+            user: user.into(),
+            password: password.into(),
+        }
+    }
+
+    pub fn user(&self) -> &str {
+        &self.user
+    }
+
+    pub(crate) fn password(&self) -> &str {
+        &self.password
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("user", &self.user)
+            .field("password", &"[redacted]")
+            .finish()
+    }
+}
+
+/// How a request authenticates to the server.
+#[derive(Clone)]
+pub enum Auth {
+    /// `-u`, or an entry from netrc: sent with the spec's [`AuthScheme`].
+    Credentials(Credentials),
+    /// `--oauth2-bearer`: a token sent up front with every request.
+    Bearer(String),
+}
+
+impl fmt::Debug for Auth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Auth::Credentials(credentials) => {
+                f.debug_tuple("Credentials").field(credentials).finish()
+            }
+            Auth::Bearer(_) => f.debug_tuple("Bearer").field(&"[redacted]").finish(),
+        }
+    }
+}
+
+/// How a user name and password are sent, wherever they came from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AuthScheme {
+    /// `--basic`: sent up front with every request.
+    #[default]
+    Basic,
+    /// `--digest`: sent in answer to the server's Digest challenge.
+    Digest,
+    /// `--anyauth`: the strongest scheme the server offers on a 401.
+    Any,
+}
+
+/// The Authorization header to send before the server has asked for one.
+pub(crate) fn preemptive(auth: &Auth, scheme: AuthScheme) -> Result<Option<HeaderValue>, Error> {
+    match (auth, scheme) {
+        (Auth::Credentials(credentials), AuthScheme::Basic) => basic(credentials).map(Some),
+        (Auth::Bearer(token), _) => sensitive(format!("Bearer {}", token)).map(Some),
+        (Auth::Credentials(_), AuthScheme::Digest | AuthScheme::Any) => Ok(None),
+    }
+}
+
+/// The Authorization header answering a 401, or `None` when the server
+/// offered no scheme `auth` can use.
+pub(crate) fn respond(
+    auth: &Auth,
+    scheme: AuthScheme,
+    headers: &HeaderMap,
+    method: &Method,
+    url: &Url,
+) -> Result<Option<HeaderValue>, Error> {
+    let challenges: Vec<Challenge> = headers
+        .get_all(WWW_AUTHENTICATE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(parse_challenges)
+        .collect();
+    let find = |scheme: &str| {
+        challenges
+            .iter()
+            .find(|challenge| challenge.scheme.eq_ignore_ascii_case(scheme))
+    };
+    let credentials = match auth {
+        Auth::Credentials(credentials) => credentials,
+        Auth::Bearer(_) => return Ok(None),
+    };
+    match scheme {
+        AuthScheme::Digest => match find("Digest") {
+            Some(challenge) => digest(credentials, challenge, method, url).map(Some),
+            None => Ok(None),
+        },
+        AuthScheme::Any => match (find("Digest"), find("Basic")) {
+            (Some(challenge), _) => digest(credentials, challenge, method, url).map(Some),
+            (None, Some(_)) => basic(credentials).map(Some),
+            (None, None) => Ok(None),
+        },
+        AuthScheme::Basic => Ok(None),
+    }
+}
+
+fn basic(credentials: &Credentials) -> Result<HeaderValue, Error> {
+    let encoded = STANDARD.encode(format!("{}:{}", credentials.user, credentials.password));
+    sensitive(format!("Basic {}", encoded))
+}
+
+fn sensitive(value: String) -> Result<HeaderValue, Error> {
+    let mut value = HeaderValue::from_str(&value)
+        .map_err(|_| Error::BadInput("Credentials contain invalid characters.".to_string()))?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+/// Answers a Digest challenge as described in RFC 7616.
+fn digest(
+    credentials: &Credentials,
+    challenge: &Challenge,
+    method: &Method,
+    url: &Url,
+) -> Result<HeaderValue, Error> {
+    digest_with_cnonce(credentials, challenge, method, url, &cnonce())
+}
+
+fn digest_with_cnonce(
+    credentials: &Credentials,
+    challenge: &Challenge,
+    method: &Method,
+    url: &Url,
+    cnonce: &str,
+) -> Result<HeaderValue, Error> {
+    let realm = challenge.param("realm").unwrap_or_default();
+    let nonce = challenge.param("nonce").unwrap_or_default();
+    let algorithm = challenge.param("algorithm").unwrap_or("MD5");
+    let (hash, session): (fn(&str) -> String, bool) = match algorithm.to_ascii_uppercase().as_str()
+    {
+        "MD5" => (md5_hex, false),
+        "MD5-SESS" => (md5_hex, true),
+        "SHA-256" => (sha256_hex, false),
+        "SHA-256-SESS" => (sha256_hex, true),
+        _ => {
+            return Err(Error::Unsupported(format!(
+                "Digest algorithm {}.",
+                algorithm
+            )))
+        }
+    };
+    let qop = challenge.param("qop").map(|qop| {
+        qop.split(',')
+            .map(str::trim)
+            .any(|option| option.eq_ignore_ascii_case("auth"))
+    });
+    if qop == Some(false) {
+        return Err(Error::Unsupported(
+            "Digest quality of protection other than auth.".to_string(),
+        ));
+    }
+    let uri = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    let nc = "00000001";
+
+    let mut ha1 = hash(&format!(
+        "{}:{}:{}",
+        credentials.user, realm, credentials.password
+    ));
+    if session {
+        ha1 = hash(&format!("{}:{}:{}", ha1, nonce, cnonce));
+    }
+    let ha2 = hash(&format!("{}:{}", method, uri));
+    let response = match qop {
+        Some(_) => hash(&format!("{}:{}:{}:{}:auth:{}", ha1, nonce, nc, cnonce, ha2)),
+        None => hash(&format!("{}:{}:{}", ha1, nonce, ha2)),
+    };
+
+    let userhash = challenge
+        .param("userhash")
+        .is_some_and(|userhash| userhash.eq_ignore_ascii_case("true"));
+    let username = match userhash {
+        true => hash(&format!("{}:{}", credentials.user, realm)),
+        false => credentials.user.clone(),
+    };
+
+    let mut header = format!(
+        "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", algorithm={}, response=\"{}\"",
+        quote(&username),
+        quote(realm),
+        quote(nonce),
+        quote(&uri),
+        algorithm,
+        response
+    );
+    if qop.is_some() {
+        header.push_str(&format!(", qop=auth, nc={}, cnonce=\"{}\"", nc, cnonce));
+    }
+    if let Some(opaque) = challenge.param("opaque") {
+        header.push_str(&format!(", opaque=\"{}\"", quote(opaque)));
+    }
+    if userhash {
+        header.push_str(", userhash=true");
+    }
+    sensitive(header)
+}
+
+fn md5_hex(input: &str) -> String {
+    hex(&Md5::digest(input.as_bytes()))
+}
+
+fn sha256_hex(input: &str) -> String {
+    hex(&Sha256::digest(input.as_bytes()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn cnonce() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos())
+            .unwrap_or_default(),
+    );
+    format!("{:016x}", hasher.finish())
+}
+
+fn quote(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// One scheme offered in a WWW-Authenticate header.
+#[derive(Debug)]
+struct Challenge {
+    scheme: String,
+    params: Vec<(String, String)>,
+}
+
+impl Challenge {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Splits a WWW-Authenticate value into its challenges. A header may hold
+/// several, e.g. `Basic realm="a", Digest realm="b", nonce="c"`.
+fn parse_challenges(value: &str) -> Vec<Challenge> {
+    let mut challenges: Vec<Challenge> = Vec::new();
+    let mut rest = value.trim();
+    while !rest.is_empty() {
+        rest = rest.trim_start_matches([',', ' ', '\t']);
+        let token_end = rest
+            .find(|c: char| c == '=' || c == ',' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let token = &rest[..token_end];
+        let after = rest[token_end..].trim_start();
+        if token.is_empty() {
+            break;
+        }
+        if let Some(value_start) = after.strip_prefix('=') {
+            let (value, remaining) = parse_param_value(value_start.trim_start());
+            if let Some(challenge) = challenges.last_mut() {
+                challenge.params.push((token.to_string(), value));
+            }
+            rest = remaining;
+        } else {
+            challenges.push(Challenge {
+                scheme: token.to_string(),
+                params: Vec::new(),
+            });
+            rest = after;
+        }
+    }
+    challenges
+}
+
+/// Reads a token or quoted-string value, returning it and what follows.
+fn parse_param_value(input: &str) -> (String, &str) {
+    if let Some(quoted) = input.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = quoted.char_indices();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '\\' => {
+                    if let Some((_, escaped)) = chars.next() {
+                        value.push(escaped);
+                    }
+                }
+                '"' => return (value, &quoted[index + 1..]),
+                c => value.push(c),
+            }
+        }
+        (value, "")
+    } else {
+        let end = input.find(',').unwrap_or(input.len());
+        (input[..end].trim().to_string(), &input[end..])
+    }
+}
+
+/// `url` with any password replaced, for printing.
+pub(crate) fn redact_url(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut parsed) if parsed.password().is_some() => {
+            let _ = parsed.set_password(Some("*****"));
+            parsed.to_string()
+        }
+        _ => url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rfc_challenge(algorithm: &str) -> Challenge {
+        let header = format!(
+            "Digest realm=\"http-auth@example.org\", qop=\"auth, auth-int\", algorithm={}, \
+             nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", \
+             opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\"",
+            algorithm
+        );
+        parse_challenges(&header).remove(0)
+    }
+
+    fn answer(algorithm: &str) -> String {
+        let header = digest_with_cnonce(
+            &Credentials::new("Mufasa", "Circle of Life"),
+            &rfc_challenge(algorithm),
+            &Method::GET,
+            &Url::parse("http://www.example.org/dir/index.html").unwrap(),
+            "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ",
+        )
+        .unwrap();
+        header.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn parses_several_challenges() {
+        let challenges =
+            parse_challenges(r#"Basic realm="a, b", Digest realm="c", nonce=xyz, qop="auth""#);
+        assert_eq!(challenges.len(), 2);
+        assert_eq!(challenges[0].scheme, "Basic");
+        assert_eq!(challenges[0].param("realm"), Some("a, b"));
+        assert_eq!(challenges[1].scheme, "Digest");
+        assert_eq!(challenges[1].param("REALM"), Some("c"));
+        assert_eq!(challenges[1].param("nonce"), Some("xyz"));
+        assert_eq!(challenges[1].param("qop"), Some("auth"));
+    }
+
+    #[test]
+    fn parses_escaped_quotes() {
+        let challenges = parse_challenges(r#"Digest realm="say \"hi\"", nonce="n""#);
+        assert_eq!(challenges[0].param("realm"), Some(r#"say "hi""#));
+        assert_eq!(challenges[0].param("nonce"), Some("n"));
+    }
+
+    #[test]
+    fn answers_rfc_7616_md5_example() {
+        let header = answer("MD5");
+        assert!(header.starts_with("Digest username=\"Mufasa\", realm=\"http-auth@example.org\""));
+        assert!(header.contains("uri=\"/dir/index.html\""));
+        assert!(header.contains("response=\"8ca523f5e9506fed4657c9700eebdbec\""));
+        assert!(header.contains("qop=auth, nc=00000001"));
+        assert!(header.contains("opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\""));
+    }
+
+    #[test]
+    fn answers_rfc_7616_sha256_example() {
+        assert!(answer("SHA-256").contains(
+            "response=\"753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1\""
+        ));
+    }
+
+    #[test]
+    fn answers_rfc_2617_example_without_opaque() {
+        let challenge = parse_challenges(
+            r#"Digest realm="testrealm@host.com", qop="auth", nonce="dcd98b7102dd2f0e8b11d0f600bfb0c093""#,
+        )
+        .remove(0);
+        let header = digest_with_cnonce(
+            &Credentials::new("Mufasa", "Circle Of Life"),
+            &challenge,
+            &Method::GET,
+            &Url::parse("http://host.com/dir/index.html").unwrap(),
+            "0a4f113b",
+        )
+        .unwrap();
+        let header = header.to_str().unwrap();
+        assert!(header.contains("response=\"6629fae49393a05397450978507c4ef1\""));
+        assert!(!header.contains("opaque"));
+    }
+
+    #[test]
+    fn any_prefers_digest_over_basic() {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            WWW_AUTHENTICATE,
+            HeaderValue::from_static("Basic realm=\"r\""),
+        );
+        headers.append(
+            WWW_AUTHENTICATE,
+            HeaderValue::from_static("Digest realm=\"r\", nonce=\"n\""),
+        );
+        let url = Url::parse("http://example.org/").unwrap();
+        let auth = Auth::Credentials(Credentials::new("user", "pass"));
+        let answer = respond(&auth, AuthScheme::Any, &headers, &Method::GET, &url)
+            .unwrap()
+            .unwrap();
+        assert!(answer.to_str().unwrap().starts_with("Digest "));
+        assert_eq!(
+            respond(&auth, AuthScheme::Basic, &headers, &Method::GET, &url).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_digest_algorithm() {
+        assert!(matches!(
+            digest(
+                &Credentials::new("u", "p"),
+                &rfc_challenge("SHA-512"),
+                &Method::GET,
+                &Url::parse("http://example.org/").unwrap(),
+            ),
+            Err(Error::Unsupported(_))
+        ));
+    }
+}
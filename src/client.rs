@@ -0,0 +1,639 @@
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE, COOKIE, HOST,
+    LOCATION, USER_AGENT,
+};
+use reqwest::redirect::Policy;
+use reqwest::tls::TlsInfo;
+use reqwest::{Method, StatusCode, Version};
+use url::Url;
+
+use crate::auth::{self, redact_url, Auth, AuthScheme};
+use crate::cookie::CookieJar;
+use crate::error::Error;
+use crate::form::build_form;
+use crate::headers::HeaderArg;
+use crate::request::{Body, RequestSpec};
+use crate::resolve::{self, Route};
+use crate::retry::retry;
+use crate::tls;
+use crate::upload::{spool_stdin, upload_body};
+use crate::verbose;
+
+/// The result of a transfer whose response head has arrived. The body is
+/// left on the connection until it is read with [`Outcome::text`] or
+/// [`Outcome::copy_to`].
+#[derive(Debug)]
+pub struct Outcome {
+    pub url: Url,
+    pub version: Version,
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    /// How many redirects were followed to reach `url`.
+    pub num_redirects: u32,
+    /// The address of the server that sent the response.
+    pub remote_addr: Option<SocketAddr>,
+    /// The DER-encoded certificate the server presented over TLS.
+    pub peer_certificate: Option<Vec<u8>>,
+    /// The cookie jar after every response, when cookies are enabled.
+    pub cookies: Option<CookieJar>,
+    /// Time from the start of the transfer until the final response head
+    /// arrived, redirects included.
+    pub time_starttransfer: Duration,
+    body: BodySource,
+    pace: Pace,
+    downloaded: u64,
+}
+
+/// Where the body of the final response is read from.
+#[derive(Debug)]
+enum BodySource {
+    Direct(Response),
+    /// Chunks read by a helper thread, so that `--speed-time` can give up
+    /// on a stalled server; a blocking read cannot be interrupted.
+    Watched {
+        chunks: Receiver<std::io::Result<Vec<u8>>>,
+        pending: Vec<u8>,
+        offset: usize,
+        finished: bool,
+    },
+}
+
+impl BodySource {
+    fn new(response: Response, watched: bool) -> Self {
+        if !watched {
+            return BodySource::Direct(response);
+        }
+        let (sender, chunks) = mpsc::sync_channel(4);
+        let mut response = response;
+        std::thread::spawn(move || loop {
+            let mut chunk = vec![0u8; 16384];
+            let result = match response.read(&mut chunk) {
+                Ok(read) => {
+                    chunk.truncate(read);
+                    Ok(chunk)
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => Err(e),
+            };
+            let last = !matches!(&result, Ok(chunk) if !chunk.is_empty());
+            if sender.send(result).is_err() || last {
+                break;
+            }
+        });
+        BodySource::Watched {
+            chunks,
+            pending: Vec::new(),
+            offset: 0,
+            finished: false,
+        }
+    }
+}
+
+/// Enforces `--max-time` and `--speed-limit`. Both are checked as each
+/// chunk arrives and, with a speed limit, whenever a `--speed-time` window
+/// ends without one.
+#[derive(Debug)]
+struct Pace {
+    started: Instant,
+    max_time: Option<Duration>,
+    speed_limit: Option<(u64, Duration)>,
+    window_started: Instant,
+    /// The byte count when the current window started.
+    window_total: u64,
+}
+
+impl Pace {
+    fn new(spec: &RequestSpec, started: Instant) -> Self {
+        Pace {
+            started,
+            max_time: spec.max_time(),
+            speed_limit: spec.speed_limit(),
+            window_started: Instant::now(),
+            window_total: 0,
+        }
+    }
+
+    /// What is left of `--max-time`, or an error once it has run out.
+    fn remaining(&self) -> Result<Option<Duration>, Error> {
+        match self.max_time {
+            Some(max_time) => match max_time.checked_sub(self.started.elapsed()) {
+                Some(remaining) if !remaining.is_zero() => Ok(Some(remaining)),
+                _ => Err(Error::OperationTimeout(max_time)),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Starts a new speed window for a count that is now at `total`.
+    fn restart(&mut self, total: u64) {
+        self.window_started = Instant::now();
+        self.window_total = total;
+    }
+
+    /// Checks the limits now that `total` bytes have been transferred.
+    fn record(&mut self, total: u64) -> Result<(), Error> {
+        self.remaining()?;
+        if let Some((limit, time)) = self.speed_limit {
+            let elapsed = self.window_started.elapsed();
+            if elapsed >= time {
+                let bytes = total.saturating_sub(self.window_total);
+                if (bytes as f64) / elapsed.as_secs_f64() < limit as f64 {
+                    return Err(Error::SpeedTooLow(limit, time));
+                }
+                self.restart(total);
+            }
+        }
+        Ok(())
+    }
+
+    /// Waits for a helper thread to deliver, checking the limits against
+    /// `progress` whenever a speed window ends in the meantime. Only called
+    /// with a speed limit set.
+    fn wait<T>(&mut self, receiver: &Receiver<T>, progress: impl Fn() -> u64) -> Result<T, Error> {
+        let time = self.speed_limit.map_or(Duration::MAX, |(_, time)| time);
+        loop {
+            let mut timeout = time.saturating_sub(self.window_started.elapsed());
+            if let Some(remaining) = self.remaining()? {
+                timeout = timeout.min(remaining);
+            }
+            match receiver.recv_timeout(timeout) {
+                Ok(value) => return Ok(value),
+                Err(RecvTimeoutError::Timeout) => self.record(progress())?,
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(Error::Transfer(
+                        "the transfer stopped unexpectedly".to_string(),
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Names a failed body read after the limit that caused it, if any.
+    fn read_error(&self, e: std::io::Error) -> Error {
+        if let Err(e) = self.remaining() {
+            return e;
+        }
+        let timed_out = e.kind() == std::io::ErrorKind::TimedOut
+            || e.get_ref()
+                .and_then(|inner| inner.downcast_ref::<reqwest::Error>())
+                .is_some_and(|inner| inner.is_timeout());
+        match timed_out {
+            true => Error::Timeout(e.to_string()),
+            false => Error::Transfer(e.to_string()),
+        }
+    }
+
+    /// Names a timeout reported by reqwest after the limit that caused it.
+    fn timeout_error(&self, spec: &RequestSpec, e: reqwest::Error) -> Error {
+        if !e.is_timeout() {
+            return Error::from(e);
+        }
+        if let Err(e) = self.remaining() {
+            return e;
+        }
+        match spec.connect_timeout() {
+            Some(connect_timeout) if e.is_connect() => Error::ConnectTimeout(connect_timeout),
+            _ => Error::from(e),
+        }
+    }
+}
+
+impl Outcome {
+    /// Fails with [`Error::HttpStatus`] for 4xx and 5xx responses. Redirects
+    /// that were not followed are not errors.
+    pub fn error_for_status(&self) -> Result<(), Error> {
+        if self.status.is_client_error() || self.status.is_server_error() {
+            Err(Error::HttpStatus(self.status))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Time since the transfer started, redirects included.
+    pub fn elapsed(&self) -> Duration {
+        self.pace.started.elapsed()
+    }
+
+    /// How many body bytes have been read so far.
+    pub fn size_download(&self) -> u64 {
+        self.downloaded
+    }
+
+    /// Reads the rest of the body as text.
+    pub fn text(mut self) -> Result<String, Error> {
+        if let BodySource::Direct(response) = self.body {
+            return Ok(response.text()?);
+        }
+        let mut body = Vec::new();
+        self.copy_to(&mut body)?;
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+
+    /// Reads up to `limit` bytes of the body, stopping early at its end.
+    pub fn read_prefix(&mut self, limit: usize) -> Result<Vec<u8>, Error> {
+        let mut prefix = vec![0u8; limit];
+        let mut filled = 0;
+        while filled < limit {
+            match self.read_chunk(&mut prefix[filled..])? {
+                0 => break,
+                read => filled += read,
+            }
+        }
+        prefix.truncate(filled);
+        Ok(prefix)
+    }
+
+    /// Streams the rest of the body into `writer`, returning the number of
+    /// bytes copied.
+    pub fn copy_to<W: Write + ?Sized>(&mut self, writer: &mut W) -> Result<u64, Error> {
+        let mut buffer = [0u8; 8192];
+        let mut copied = 0;
+        loop {
+            let read = self.read_chunk(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            writer
+                .write_all(&buffer[..read])
+                .map_err(|e| Error::Write(e.to_string()))?;
+            copied += read as u64;
+        }
+        writer.flush().map_err(|e| Error::Write(e.to_string()))?;
+        Ok(copied)
+    }
+
+    fn read_chunk(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        let read = match &mut self.body {
+            BodySource::Direct(response) => loop {
+                match response.read(buffer) {
+                    Ok(read) => break read,
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(self.pace.read_error(e)),
+                }
+            },
+            BodySource::Watched {
+                chunks,
+                pending,
+                offset,
+                finished,
+            } => {
+                if *offset == pending.len() && !*finished {
+                    let downloaded = self.downloaded;
+                    *pending = self
+                        .pace
+                        .wait(chunks, || downloaded)?
+                        .map_err(|e| self.pace.read_error(e))?;
+                    *offset = 0;
+                    *finished = pending.is_empty();
+                }
+                let read = buffer.len().min(pending.len() - *offset);
+                buffer[..read].copy_from_slice(&pending[*offset..*offset + read]);
+                *offset += read;
+                read
+            }
+        };
+        self.downloaded += read as u64;
+        self.pace.record(self.downloaded)?;
+        Ok(read)
+    }
+}
+
+/// Validates the spec, sends the request and waits for the response head,
+/// retrying according to the spec's [`RetryPolicy`](crate::RetryPolicy).
+pub fn execute(spec: &RequestSpec) -> Result<Outcome, Error> {
+    // An upload from stdin can only be read once, so it is spooled to disk
+    // whenever it may have to be sent again: on a retry, after a redirect or
+    // in answer to an authentication challenge.
+    let spooled = if spec.retry_policy().retries > 0
+        || spec.follow_redirects()
+        || spec.auth_scheme() != AuthScheme::Basic
+    {
+        spool_stdin(spec)?
+    } else {
+        None
+    };
+    let spec = spooled.as_ref().map_or(spec, |(_, spec)| spec);
+
+    let (parsed_url, route) = prepare(spec)?;
+    let mut route = Some(route);
+    retry(spec.retry_policy(), || {
+        let route = match route.take() {
+            Some(route) => route,
+            None => resolve::route(spec, &parsed_url)?,
+        };
+        transfer(spec, &parsed_url, route)
+    })
+}
+
+/// Checks the spec before anything is sent and resolves the target host,
+/// returning the parsed URL and where its first hop connects to.
+pub(crate) fn prepare(spec: &RequestSpec) -> Result<(Url, Route), Error> {
+    let url = spec.url();
+    if (!url.starts_with("http://")) && (!url.starts_with("https://")) {
+        return Err(Error::UnsupportedProtocol);
+    }
+
+    let parsed_url = Url::parse(url).map_err(Error::from_url)?;
+
+    let method = spec.request_method()?;
+    if spec.body().is_none() && method == Method::POST {
+        return Err(Error::BadInput(
+            "POST method requires data to be specified with -d.".to_string(),
+        ));
+    }
+
+    if let Some(Body::Json(json_data)) = spec.body() {
+        if let Err(e) = serde_json::from_str::<serde_json::Value>(json_data) {
+            return Err(Error::BadInput(format!("Invalid JSON: {}", e)));
+        }
+    }
+
+    let route = resolve::route(spec, &parsed_url)?;
+    Ok((parsed_url, route))
+}
+
+/// Sends the request, following redirects when asked to. The first hop
+/// connects along `first_route`; later hops are resolved as they come.
+pub(crate) fn transfer(
+    spec: &RequestSpec,
+    parsed_url: &Url,
+    first_route: Route,
+) -> Result<Outcome, Error> {
+    let mut pace = Pace::new(spec, Instant::now());
+    let mut method = spec.request_method()?;
+    let mut url = parsed_url.clone();
+    let mut route = Some(first_route);
+    let mut send_body = true;
+    let mut num_redirects = 0;
+    let netrc_auth = match (spec.auth(), spec.netrc(), parsed_url.host_str()) {
+        (None, Some(netrc), Some(host)) if parsed_url.password().is_none() => {
+            let user = Some(parsed_url.username()).filter(|user| !user.is_empty());
+            netrc.credentials(host, user).map(Auth::Credentials)
+        }
+        _ => None,
+    };
+    let auth = spec.auth().or(netrc_auth.as_ref());
+    let mut authorization = match auth {
+        Some(auth) => auth::preemptive(auth, spec.auth_scheme())?,
+        None => None,
+    };
+    let mut answered_challenge = false;
+    let mut cookies = spec.cookies().cloned();
+    loop {
+        let hop_route = match route.take() {
+            Some(route) => route,
+            None => resolve::route(spec, &url)?,
+        };
+        let (client, wire_url) = connect(spec, &url, &hop_route)?;
+        let same_origin = url.host_str() == parsed_url.host_str()
+            && url.port_or_known_default() == parsed_url.port_or_known_default();
+        let uploaded = Arc::new(AtomicU64::new(0));
+        let mut request = build_request(
+            &client,
+            spec,
+            &method,
+            &wire_url,
+            send_body,
+            same_origin,
+            authorization.as_ref(),
+            &uploaded,
+        )?;
+        if let Some(cookie) = cookies.as_ref().and_then(|jar| jar.header(&url)) {
+            if !request.headers().contains_key(COOKIE) {
+                let cookie = HeaderValue::from_str(&cookie)
+                    .map_err(|_| Error::BadInput(format!("Invalid cookie: {}", cookie)))?;
+                request.headers_mut().insert(COOKIE, cookie);
+            }
+        }
+        if wire_url != url {
+            if let Some(host) = url.host_str() {
+                let authority = match url.port() {
+                    Some(port) => format!("{}:{}", host, port),
+                    None => host.to_string(),
+                };
+                if !request.headers().contains_key(HOST) {
+                    let authority = HeaderValue::from_str(&authority)
+                        .map_err(|_| Error::MalformedUrl(authority.clone()))?;
+                    request.headers_mut().insert(HOST, authority);
+                }
+            }
+        }
+        if spec.verbose() {
+            verbose::request(&request);
+        }
+        let started = Instant::now();
+        *request.timeout_mut() = pace.remaining()?;
+        let response = match spec.speed_limit() {
+            // The request runs on a helper thread so a server that never
+            // answers can be given up on after `--speed-time`.
+            Some(_) => {
+                let (sender, receiver) = mpsc::channel();
+                std::thread::spawn(move || {
+                    let _ = sender.send(client.execute(request));
+                });
+                pace.restart(0);
+                pace.wait(&receiver, || uploaded.load(Ordering::Relaxed))?
+            }
+            None => client.execute(request),
+        }
+        .map_err(|e| pace.timeout_error(spec, e))?;
+        if spec.verbose() {
+            verbose::response(&response, started.elapsed());
+        }
+        let status = response.status();
+        if let Some(jar) = &mut cookies {
+            jar.store(&url, response.headers());
+        }
+
+        if status == StatusCode::UNAUTHORIZED && same_origin && !answered_challenge {
+            if let Some(auth) = auth {
+                if let Some(answer) =
+                    auth::respond(auth, spec.auth_scheme(), response.headers(), &method, &url)?
+                {
+                    if spec.verbose() {
+                        eprintln!("* Server asked for authentication, sending credentials");
+                    }
+                    authorization = Some(answer);
+                    answered_challenge = true;
+                    route = Some(hop_route);
+                    continue;
+                }
+            }
+        }
+
+        let location = match response.headers().get(LOCATION) {
+            Some(location) if spec.follow_redirects() && status.is_redirection() => location,
+            _ => {
+                // The body gets speed windows of its own.
+                pace.restart(0);
+                return Ok(Outcome {
+                    url,
+                    version: response.version(),
+                    status,
+                    headers: response.headers().clone(),
+                    num_redirects,
+                    remote_addr: response.remote_addr(),
+                    peer_certificate: response
+                        .extensions()
+                        .get::<TlsInfo>()
+                        .and_then(|info| info.peer_certificate())
+                        .map(|certificate| certificate.to_vec()),
+                    cookies,
+                    time_starttransfer: pace.started.elapsed(),
+                    body: BodySource::new(response, spec.speed_limit().is_some()),
+                    pace,
+                    downloaded: 0,
+                });
+            }
+        };
+        if spec.max_redirects().is_some_and(|max| num_redirects >= max) {
+            return Err(Error::TooManyRedirects(num_redirects));
+        }
+        let next = location
+            .to_str()
+            .ok()
+            .and_then(|location| url.join(location).ok())
+            .ok_or_else(|| Error::MalformedUrl("Invalid redirect location.".to_string()))?;
+        if next.scheme() != "http" && next.scheme() != "https" {
+            return Err(Error::UnsupportedProtocol);
+        }
+
+        // 301 and 302 only turn POST into GET, 303 turns everything but HEAD
+        // into GET, and 307/308 resend the original request untouched.
+        match status.as_u16() {
+            301 | 302 if method == Method::POST => {
+                method = Method::GET;
+                send_body = false;
+            }
+            303 if method != Method::HEAD => {
+                method = Method::GET;
+                send_body = false;
+            }
+            _ => {}
+        }
+
+        if spec.verbose() {
+            eprintln!(
+                "* Redirect {}: {} -> {} ({})",
+                num_redirects + 1,
+                redact_url(url.as_str()),
+                redact_url(next.as_str()),
+                status
+            );
+        }
+        num_redirects += 1;
+        url = next;
+        authorization = match auth {
+            Some(auth) => auth::preemptive(auth, spec.auth_scheme())?,
+            None => None,
+        };
+        answered_challenge = false;
+    }
+}
+
+/// Builds a client that connects to the addresses of `route`. Hostnames
+/// are pinned to them so the Host header and TLS SNI stay unchanged; only
+/// the URL actually sent on the wire changes when the port or an IP host
+/// has to.
+fn connect(spec: &RequestSpec, url: &Url, route: &Route) -> Result<(Client, Url), Error> {
+    let mut wire_url = url.clone();
+    let mut builder = Client::builder()
+        .redirect(Policy::none())
+        .tls_info(true)
+        .timeout(None)
+        .connect_timeout(spec.connect_timeout())
+        .local_address(route.local_address);
+    match url.domain() {
+        Some(domain) if !route.addrs.is_empty() => {
+            builder = builder.resolve_to_addrs(domain, &route.addrs)
+        }
+        Some(_) => {}
+        None => {
+            if let Some(addr) = route.addrs.first() {
+                let _ = wire_url.set_ip_host(addr.ip());
+            }
+        }
+    }
+    builder = match spec.proxy().proxy_for(url) {
+        // The pinned key would be asked of the proxy's own TLS too.
+        Some(proxy_url)
+            if proxy_url.scheme() == "https" && spec.tls().pinned_public_key.is_some() =>
+        {
+            return Err(Error::Unsupported(
+                "--pinnedpubkey: the key cannot be checked through an HTTPS proxy.".to_string(),
+            ));
+        }
+        Some(proxy_url) => builder.proxy(spec.proxy().build(proxy_url)?),
+        None => builder.no_proxy(),
+    };
+    if url.port_or_known_default() != Some(route.port) {
+        let _ = wire_url.set_port(Some(route.port));
+    }
+    builder = tls::configure(builder, spec.tls())?;
+    Ok((builder.build()?, wire_url))
+}
+
+/// Builds one hop of the transfer. Credentials are only sent while the
+/// request stays on the host the user asked for, and `-T` uploads count
+/// the bytes they send in `uploaded`.
+#[allow(clippy::too_many_arguments)]
+fn build_request(
+    client: &Client,
+    spec: &RequestSpec,
+    method: &Method,
+    url: &Url,
+    send_body: bool,
+    same_origin: bool,
+    authorization: Option<&HeaderValue>,
+    uploaded: &Arc<AtomicU64>,
+) -> Result<reqwest::blocking::Request, Error> {
+    let request = client
+        .request(method.clone(), url.clone())
+        .header(USER_AGENT, concat!("curl/", env!("CARGO_PKG_VERSION")))
+        .header(ACCEPT, "*/*");
+    let request = match spec.body() {
+        Some(Body::Json(json_data)) if send_body => request
+            .header("Content-Type", "application/json")
+            .body(json_data.to_string()),
+        Some(Body::Form(data)) if send_body => request
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(data.clone()),
+        Some(Body::Multipart(parts)) if send_body => request.multipart(build_form(parts)?),
+        Some(Body::Upload(path)) if send_body => request.body(upload_body(path, uploaded.clone())?),
+        _ => request,
+    };
+
+    let mut request = request.build()?;
+    if let Some(authorization) = authorization {
+        request
+            .headers_mut()
+            .insert(AUTHORIZATION, authorization.clone());
+    }
+    let mut replaced: HashSet<HeaderName> = HashSet::new();
+    for header in spec.headers() {
+        match header {
+            HeaderArg::Set(name, value) => {
+                if replaced.insert(name.clone()) {
+                    request.headers_mut().remove(name);
+                }
+                request.headers_mut().append(name.clone(), value.clone());
+            }
+            HeaderArg::Remove(name) => {
+                request.headers_mut().remove(name);
+            }
+        }
+    }
+    if !same_origin {
+        request.headers_mut().remove(AUTHORIZATION);
+    }
+    Ok(request)
+}
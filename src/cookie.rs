@@ -0,0 +1,461 @@
+use std::cmp::Reverse;
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use reqwest::header::{HeaderMap, SET_COOKIE};
+use url::Url;
+
+use crate::error::Error;
+
+/// Cookies sent with each request and updated from `Set-Cookie` responses.
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar {
+    cookies: Vec<Cookie>,
+    /// `-b name=value` pairs, sent to every host and never saved.
+    fixed: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone)]
+struct Cookie {
+    name: String,
+    value: String,
+    /// Lowercase, without a leading dot.
+    domain: String,
+    /// Set without a `Domain` attribute, so subdomains do not match.
+    host_only: bool,
+    path: String,
+    secure: bool,
+    http_only: bool,
+    /// Seconds since the Unix epoch; `None` for a session cookie.
+    expires: Option<u64>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        CookieJar::default()
+    }
+
+    /// Adds `name=value; name2=value2` pairs to send with every request.
+    pub fn add_pairs(&mut self, pairs: &str) {
+        for pair in pairs
+            .split(';')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+        {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            self.fixed
+                .push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    /// Loads a Netscape-format cookie file; a missing file adds nothing.
+    /// Session cookies are dropped unless `keep_session` is set.
+    pub fn load(&mut self, path: &Path, keep_session: bool) -> Result<(), Error> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => {
+                return Err(Error::BadInput(format!(
+                    "Failed to read cookies from {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        };
+        let now = now();
+        for cookie in contents.lines().filter_map(Cookie::parse_netscape) {
+            if (keep_session || cookie.expires.is_some()) && !cookie.is_expired(now) {
+                self.insert(cookie);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes every unexpired cookie to `path` in Netscape format.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let now = now();
+        let mut contents = String::from(
+            "# Netscape HTTP Cookie File\n# This file was generated by curl. Edit at your own risk.\n\n",
+        );
+        for cookie in self.cookies.iter().filter(|cookie| !cookie.is_expired(now)) {
+            contents.push_str(&cookie.to_netscape());
+            contents.push('\n');
+        }
+        std::fs::write(path, contents)
+            .map_err(|e| Error::Write(format!("{}: {}", path.display(), e)))
+    }
+
+    /// The `Cookie` header value for a request to `url`, longest paths first.
+    pub(crate) fn header(&self, url: &Url) -> Option<String> {
+        let host = url.host_str()?.to_ascii_lowercase();
+        let secure = url.scheme() == "https";
+        let now = now();
+        let mut matching: Vec<&Cookie> = self
+            .cookies
+            .iter()
+            .filter(|cookie| cookie.matches(&host, url.path(), secure, now))
+            .collect();
+        matching.sort_by_key(|cookie| Reverse(cookie.path.len()));
+        let pairs: Vec<String> = self
+            .fixed
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .chain(
+                matching
+                    .iter()
+                    .map(|cookie| format!("{}={}", cookie.name, cookie.value)),
+            )
+            .collect();
+        if pairs.is_empty() {
+            None
+        } else {
+            Some(pairs.join("; "))
+        }
+    }
+
+    /// Applies the `Set-Cookie` headers of a response from `url`.
+    pub(crate) fn store(&mut self, url: &Url, headers: &HeaderMap) {
+        let Some(host) = url.host_str() else {
+            return;
+        };
+        let now = now();
+        for header in headers.get_all(SET_COOKIE) {
+            let Ok(header) = header.to_str() else {
+                continue;
+            };
+            let Some(cookie) = Cookie::parse_set_cookie(header, host, url.path(), now) else {
+                continue;
+            };
+            if cookie.secure && url.scheme() != "https" {
+                continue;
+            }
+            if cookie.is_expired(now) {
+                self.remove(&cookie);
+            } else {
+                self.insert(cookie);
+            }
+        }
+    }
+
+    fn insert(&mut self, cookie: Cookie) {
+        self.remove(&cookie);
+        self.cookies.push(cookie);
+    }
+
+    fn remove(&mut self, cookie: &Cookie) {
+        self.cookies.retain(|other| {
+            !(other.name == cookie.name
+                && other.domain == cookie.domain
+                && other.path == cookie.path)
+        });
+    }
+}
+
+impl Cookie {
+    fn parse_set_cookie(header: &str, host: &str, request_path: &str, now: u64) -> Option<Self> {
+        let mut attributes = header.split(';');
+        let (name, value) = attributes.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        let host = host.to_ascii_lowercase();
+        let mut cookie = Cookie {
+            name: name.to_string(),
+            value: value.trim().to_string(),
+            domain: host.clone(),
+            host_only: true,
+            path: default_path(request_path),
+            secure: false,
+            http_only: false,
+            expires: None,
+        };
+        let mut max_age = None;
+        for attribute in attributes {
+            let (key, value) = attribute
+                .split_once('=')
+                .map_or((attribute.trim(), ""), |(key, value)| {
+                    (key.trim(), value.trim())
+                });
+            match key.to_ascii_lowercase().as_str() {
+                "domain" => {
+                    let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                    // A cookie for a whole top-level domain or registry is
+                    // kept for the sending host only, as curl does
+                    // without a public suffix list.
+                    if domain.is_empty() || is_public_suffix(&domain) {
+                        continue;
+                    }
+                    if !domain_matches(&host, &domain) {
+                        return None;
+                    }
+                    cookie.domain = domain;
+                    cookie.host_only = false;
+                }
+                "path" if value.starts_with('/') => cookie.path = value.to_string(),
+                "secure" => cookie.secure = true,
+                "httponly" => cookie.http_only = true,
+                "max-age" => max_age = value.parse::<i64>().ok(),
+                "expires" => cookie.expires = parse_date(value).or(cookie.expires),
+                _ => {}
+            }
+        }
+        if let Some(max_age) = max_age {
+            cookie.expires = Some(match u64::try_from(max_age) {
+                Ok(max_age) if max_age > 0 => now.saturating_add(max_age),
+                _ => 0,
+            });
+        }
+        Some(cookie)
+    }
+
+    /// One tab-separated line: domain, subdomains flag, path, secure flag,
+    /// expiry (0 for session cookies), name and value.
+    fn parse_netscape(line: &str) -> Option<Self> {
+        let (line, http_only) = match line.strip_prefix("#HttpOnly_") {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        if line.starts_with('#') {
+            return None;
+        }
+        let fields: Vec<&str> = line.trim_end_matches('\r').split('\t').collect();
+        if fields.len() < 6 {
+            return None;
+        }
+        let expires = fields[4].parse::<u64>().ok()?;
+        Some(Cookie {
+            name: fields[5].to_string(),
+            value: fields.get(6).copied().unwrap_or_default().to_string(),
+            domain: fields[0].trim_start_matches('.').to_ascii_lowercase(),
+            host_only: !fields[1].eq_ignore_ascii_case("TRUE"),
+            path: fields[2].to_string(),
+            secure: fields[3].eq_ignore_ascii_case("TRUE"),
+            http_only,
+            expires: Some(expires).filter(|expires| *expires != 0),
+        })
+    }
+
+    fn to_netscape(&self) -> String {
+        let flag = |set: bool| if set { "TRUE" } else { "FALSE" };
+        format!(
+            "{}{}{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            if self.http_only { "#HttpOnly_" } else { "" },
+            if self.host_only { "" } else { "." },
+            self.domain,
+            flag(!self.host_only),
+            self.path,
+            flag(self.secure),
+            self.expires.unwrap_or(0),
+            self.name,
+            self.value
+        )
+    }
+
+    fn matches(&self, host: &str, path: &str, secure: bool, now: u64) -> bool {
+        let domain = if self.host_only {
+            host == self.domain
+        } else {
+            domain_matches(host, &self.domain)
+        };
+        domain
+            && path_matches(path, &self.path)
+            && (secure || !self.secure)
+            && !self.is_expired(now)
+    }
+
+    fn is_expired(&self, now: u64) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+}
+
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.parse::<IpAddr>().is_err()
+            && host
+                .strip_suffix(domain)
+                .is_some_and(|prefix| prefix.ends_with('.')))
+}
+
+/// Second-level registries that a `Domain` attribute must not name. Only
+/// the common ones; the full Public Suffix List is not bundled.
+const PUBLIC_SUFFIXES: &[&str] = &[
+    "ac.uk", "co.uk", "gov.uk", "org.uk", "me.uk", "ltd.uk", "plc.uk", "net.uk", "com.au",
+    "net.au", "org.au", "edu.au", "gov.au", "co.nz", "org.nz", "co.jp", "ne.jp", "or.jp", "ac.jp",
+    "go.jp", "co.kr", "or.kr", "com.br", "net.br", "org.br", "com.cn", "net.cn", "org.cn",
+    "com.mx", "com.tr", "com.tw", "com.hk", "com.sg", "co.in", "co.za", "co.il",
+];
+
+/// Whether `domain` is a top-level domain, having no interior dot, or one
+/// of the well-known registries under one.
+fn is_public_suffix(domain: &str) -> bool {
+    !domain.contains('.') || PUBLIC_SUFFIXES.contains(&domain)
+}
+
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/')))
+}
+
+/// The directory of the request path, which scopes cookies set without a
+/// `Path` attribute.
+fn default_path(request_path: &str) -> String {
+    match request_path.rfind('/') {
+        Some(end) if end > 0 && request_path.starts_with('/') => request_path[..end].to_string(),
+        _ => "/".to_string(),
+    }
+}
+
+/// Cookie dates are HTTP dates, sometimes with dashes between the day,
+/// month and year.
+fn parse_date(value: &str) -> Option<u64> {
+    let time = httpdate::parse_http_date(value)
+        .or_else(|_| httpdate::parse_http_date(&value.replace('-', " ")))
+        .ok()?;
+    Some(
+        time.duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs()),
+    )
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    #[test]
+    fn parses_set_cookie_defaults() {
+        let cookie = Cookie::parse_set_cookie("id = abc ", "Example.COM", "/a/b/c", NOW).unwrap();
+        assert_eq!(cookie.name, "id");
+        assert_eq!(cookie.value, "abc");
+        assert_eq!(cookie.domain, "example.com");
+        assert!(cookie.host_only);
+        assert_eq!(cookie.path, "/a/b");
+        assert!(!cookie.secure && !cookie.http_only);
+        assert_eq!(cookie.expires, None);
+    }
+
+    #[test]
+    fn parses_set_cookie_attributes() {
+        let cookie = Cookie::parse_set_cookie(
+            "id=1; Domain=.example.com; Path=/docs; Secure; HttpOnly; Max-Age=60; \
+             Expires=Wed, 21 Oct 2015 07:28:00 GMT",
+            "www.example.com",
+            "/",
+            NOW,
+        )
+        .unwrap();
+        assert_eq!(cookie.domain, "example.com");
+        assert!(!cookie.host_only);
+        assert_eq!(cookie.path, "/docs");
+        assert!(cookie.secure && cookie.http_only);
+        // Max-Age wins over Expires.
+        assert_eq!(cookie.expires, Some(NOW + 60));
+    }
+
+    #[test]
+    fn parses_dashed_expiry_and_expires_on_zero_max_age() {
+        let cookie =
+            Cookie::parse_set_cookie("a=1; Expires=Wed, 21-Oct-2015 07:28:00 GMT", "h", "/", NOW)
+                .unwrap();
+        assert_eq!(cookie.expires, Some(1_445_412_480));
+        let cookie = Cookie::parse_set_cookie("a=1; Max-Age=0", "h", "/", NOW).unwrap();
+        assert!(cookie.is_expired(NOW));
+    }
+
+    #[test]
+    fn rejects_foreign_domains_and_nameless_cookies() {
+        assert!(
+            Cookie::parse_set_cookie("a=1; Domain=other.com", "example.com", "/", NOW).is_none()
+        );
+        assert!(
+            Cookie::parse_set_cookie("a=1; Domain=ample.com", "example.com", "/", NOW).is_none()
+        );
+        assert!(Cookie::parse_set_cookie("=1", "example.com", "/", NOW).is_none());
+        assert!(Cookie::parse_set_cookie("novalue", "example.com", "/", NOW).is_none());
+    }
+
+    #[test]
+    fn ignores_top_level_and_public_suffix_domains() {
+        for (header, host) in [
+            ("a=1; Domain=com", "example.com"),
+            ("a=1; Domain=.COM", "example.com"),
+            ("a=1; Domain=co.uk", "shop.example.co.uk"),
+        ] {
+            let cookie = Cookie::parse_set_cookie(header, host, "/", NOW).unwrap();
+            assert_eq!(cookie.domain, host);
+            assert!(cookie.host_only);
+            assert!(!cookie.matches("other.com", "/", false, NOW));
+        }
+        let cookie =
+            Cookie::parse_set_cookie("a=1; Domain=example.co.uk", "shop.example.co.uk", "/", NOW)
+                .unwrap();
+        assert_eq!(cookie.domain, "example.co.uk");
+        assert!(!cookie.host_only);
+    }
+
+    #[test]
+    fn round_trips_netscape_lines() {
+        let line = "#HttpOnly_.example.com\tTRUE\t/\tTRUE\t2000000000\tsid\tx y";
+        let cookie = Cookie::parse_netscape(line).unwrap();
+        assert_eq!(cookie.domain, "example.com");
+        assert!(!cookie.host_only && cookie.secure && cookie.http_only);
+        assert_eq!(cookie.expires, Some(2_000_000_000));
+        assert_eq!(cookie.value, "x y");
+        assert_eq!(cookie.to_netscape(), line);
+
+        let session = Cookie::parse_netscape("host\tFALSE\t/p\tFALSE\t0\tn\tv\r").unwrap();
+        assert!(session.host_only);
+        assert_eq!(session.expires, None);
+        assert_eq!(session.value, "v");
+    }
+
+    #[test]
+    fn skips_comments_and_short_netscape_lines() {
+        assert!(Cookie::parse_netscape("# Netscape HTTP Cookie File").is_none());
+        assert!(Cookie::parse_netscape("").is_none());
+        assert!(Cookie::parse_netscape("host\tFALSE\t/\tFALSE\tsoon\tn\tv").is_none());
+    }
+
+    #[test]
+    fn matches_paths_on_segment_boundaries() {
+        assert!(path_matches("/docs", "/docs"));
+        assert!(path_matches("/docs/a", "/docs"));
+        assert!(path_matches("/docs/a", "/docs/"));
+        assert!(path_matches("/anything", "/"));
+        assert!(!path_matches("/docsx", "/docs"));
+        assert!(!path_matches("/doc", "/docs"));
+    }
+
+    #[test]
+    fn jar_sends_matching_cookies_longest_path_first() {
+        let mut jar = CookieJar::new();
+        jar.add_pairs("fixed=1");
+        let url = Url::parse("https://www.example.com/docs/page").unwrap();
+        let mut headers = HeaderMap::new();
+        for value in [
+            "a=root; Path=/",
+            "b=docs; Path=/docs",
+            "c=secure; Secure",
+            "d=parent; Domain=example.com; Path=/",
+        ] {
+            headers.append(SET_COOKIE, value.parse().unwrap());
+        }
+        jar.store(&url, &headers);
+        assert_eq!(
+            jar.header(&url).as_deref(),
+            Some("fixed=1; b=docs; c=secure; a=root; d=parent")
+        );
+        let plain = Url::parse("http://example.com/").unwrap();
+        assert_eq!(jar.header(&plain).as_deref(), Some("fixed=1; d=parent"));
+    }
+}
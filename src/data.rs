@@ -0,0 +1,116 @@
+use std::io::Read;
+
+use crate::error::Error;
+
+/// One `-d` or `--data-urlencode` argument, in command-line order.
+#[derive(Debug, Clone)]
+pub enum DataArg {
+    /// `-d`: sent as-is, or read from a file (`@file`, `@-` for stdin)
+    /// with carriage returns and newlines removed.
+    Raw(String),
+    /// `--data-urlencode`: `content`, `=content`, `name=content`,
+    /// `@file` or `name@file`, with the content percent-encoded.
+    UrlEncode(String),
+}
+
+/// Joins the pieces with `&` into a single request body.
+pub fn join_data(args: &[DataArg]) -> Result<String, Error> {
+    let mut pieces = Vec::with_capacity(args.len());
+    for arg in args {
+        pieces.push(match arg {
+            DataArg::Raw(data) => match data.strip_prefix('@') {
+                Some(path) => read_source(path)?.replace(['\r', '\n'], ""),
+                None => data.clone(),
+            },
+            DataArg::UrlEncode(data) => url_encode_arg(data)?,
+        });
+    }
+    Ok(pieces.join("&"))
+}
+
+fn url_encode_arg(data: &str) -> Result<String, Error> {
+    let name_end = data.find(['=', '@']);
+    match name_end.map(|index| (&data[..index], &data[index..])) {
+        Some((name, rest)) if rest.starts_with('=') => {
+            let content = percent_encode(&rest[1..]);
+            if name.is_empty() {
+                Ok(content)
+            } else {
+                Ok(format!("{}={}", name, content))
+            }
+        }
+        Some((name, rest)) => {
+            let content = percent_encode(&read_source(&rest[1..])?);
+            if name.is_empty() {
+                Ok(content)
+            } else {
+                Ok(format!("{}={}", name, content))
+            }
+        }
+        None => Ok(percent_encode(data)),
+    }
+}
+
+/// Reads a whole file, or stdin when `path` is `-`.
+fn read_source(path: &str) -> Result<String, Error> {
+    let mut contents = String::new();
+    let read = if path == "-" {
+        std::io::stdin().read_to_string(&mut contents)
+    } else {
+        std::fs::File::open(path).and_then(|mut file| file.read_to_string(&mut contents))
+    };
+    read.map_err(|e| Error::BadInput(format!("Failed to read data from {}: {}", path, e)))?;
+    Ok(contents)
+}
+
+/// Percent-encodes everything except RFC 3986 unreserved characters.
+pub(crate) fn percent_encode(input: &str) -> String {
+    let mut encoded = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn joins_raw_and_encoded_in_order() {
+        let args = [
+            DataArg::Raw("a=1 2".to_string()),
+            DataArg::UrlEncode("b=x&y".to_string()),
+            DataArg::Raw("c=3".to_string()),
+        ];
+        assert_eq!(join_data(&args).unwrap(), "a=1 2&b=x%26y&c=3");
+    }
+
+    #[test]
+    fn url_encode_forms() {
+        assert_eq!(url_encode_arg("hello world").unwrap(), "hello%20world");
+        assert_eq!(url_encode_arg("=a=b").unwrap(), "a%3Db");
+        assert_eq!(url_encode_arg("name=ä/ö").unwrap(), "name=%C3%A4%2F%C3%B6");
+    }
+
+    #[test]
+    fn url_encode_reads_files() {
+        let path = std::env::temp_dir().join(format!("curl-data-{}", std::process::id()));
+        std::fs::write(&path, "x y\n").unwrap();
+        let file = path.display().to_string();
+        assert_eq!(url_encode_arg(&format!("@{}", file)).unwrap(), "x%20y%0A");
+        assert_eq!(
+            url_encode_arg(&format!("name@{}", file)).unwrap(),
+            "name=x%20y%0A"
+        );
+        let joined = join_data(&[DataArg::Raw(format!("@{}", file))]).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(joined, "x y");
+        assert!(url_encode_arg("@/nonexistent/curl-data").is_err());
+    }
+}
@@ -0,0 +1,193 @@
+use std::fmt;
+use std::time::Duration;
+
+use reqwest::StatusCode;
+
+/// Every way a transfer can fail, each tied to the exit code curl uses for
+/// the same category of failure.
+#[derive(Debug)]
+pub enum Error {
+    /// The options or input data could not be used as given.
+    BadInput(String),
+    UnsupportedProtocol,
+    InvalidIpv6Address,
+    InvalidIpv4Address,
+    InvalidPort,
+    MalformedUrl(String),
+    /// A feature the request needs is not available in this build.
+    Unsupported(String),
+    CouldNotResolveHost(String),
+    CouldNotConnect(String),
+    HttpStatus(StatusCode),
+    /// `--interface` named no usable local address.
+    Interface(String),
+    TooManyRedirects(u32),
+    /// No connection was established within `--connect-timeout`.
+    ConnectTimeout(Duration),
+    /// The transfer did not finish within `--max-time`.
+    OperationTimeout(Duration),
+    /// Fewer than the given bytes per second arrived over the given time.
+    SpeedTooLow(u64, Duration),
+    Timeout(String),
+    /// The connection broke while the response was being received.
+    Transfer(String),
+    /// The body could not be written to its destination.
+    Write(String),
+    /// The netrc file could not be read or parsed.
+    Netrc(String),
+    /// The TLS handshake with the server failed.
+    TlsHandshake(String),
+    /// The client certificate or key could not be used.
+    Certificate(String),
+    /// The server certificate could not be verified.
+    PeerVerification(String),
+    /// The CA certificates could not be loaded.
+    CaCertificate(String),
+    /// The server's public key does not match `--pinnedpubkey`.
+    PinnedPublicKey(String),
+}
+
+impl Error {
+    /// The process exit code curl reports for this failure.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::UnsupportedProtocol => 1,
+            Error::BadInput(_) => 2,
+            Error::InvalidIpv6Address
+            | Error::InvalidIpv4Address
+            | Error::InvalidPort
+            | Error::MalformedUrl(_) => 3,
+            Error::Unsupported(_) => 4,
+            Error::CouldNotResolveHost(_) => 6,
+            Error::CouldNotConnect(_) => 7,
+            Error::HttpStatus(_) => 22,
+            Error::ConnectTimeout(_)
+            | Error::OperationTimeout(_)
+            | Error::SpeedTooLow(_, _)
+            | Error::Timeout(_) => 28,
+            Error::Interface(_) => 45,
+            Error::TooManyRedirects(_) => 47,
+            Error::Write(_) => 23,
+            Error::Netrc(_) => 26,
+            Error::TlsHandshake(_) => 35,
+            Error::Certificate(_) => 58,
+            Error::PeerVerification(_) => 60,
+            Error::CaCertificate(_) => 77,
+            Error::PinnedPublicKey(_) => 90,
+            Error::Transfer(_) => 56,
+        }
+    }
+
+    pub(crate) fn from_url(e: url::ParseError) -> Self {
+        match e {
+            url::ParseError::InvalidIpv6Address => Error::InvalidIpv6Address,
+            url::ParseError::InvalidIpv4Address => Error::InvalidIpv4Address,
+            url::ParseError::InvalidPort => Error::InvalidPort,
+            e => Error::MalformedUrl(e.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::BadInput(message) => write!(f, "{}", message),
+            Error::UnsupportedProtocol => {
+                write!(f, "The URL does not have a valid base protocol.")
+            }
+            Error::InvalidIpv6Address => write!(f, "The URL contains an invalid IPv6 address."),
+            Error::InvalidIpv4Address => write!(f, "The URL contains an invalid IPv4 address."),
+            Error::InvalidPort => write!(f, "The URL contains an invalid port number."),
+            Error::MalformedUrl(message) => write!(f, "{}", message),
+            Error::Unsupported(message) => write!(f, "Not supported: {}", message),
+            Error::CouldNotResolveHost(host) => write!(
+                f,
+                "Unable to connect to the server. Perhaps the network is offline or the server hostname cannot be resolved: {}.",
+                host
+            ),
+            Error::CouldNotConnect(message) => {
+                write!(f, "Failed to connect to the server: {}", message)
+            }
+            Error::HttpStatus(status) => {
+                write!(f, "Request failed with status code: {}.", status.as_u16())
+            }
+            Error::Interface(interface) => {
+                write!(f, "Failed to bind to local interface: {}.", interface)
+            }
+            Error::TooManyRedirects(max) => {
+                write!(f, "Maximum ({}) redirects followed.", max)
+            }
+            Error::ConnectTimeout(timeout) => write!(
+                f,
+                "Connection timed out after {} milliseconds.",
+                timeout.as_millis()
+            ),
+            Error::OperationTimeout(timeout) => write!(
+                f,
+                "Operation timed out after {} milliseconds.",
+                timeout.as_millis()
+            ),
+            Error::SpeedTooLow(limit, time) => write!(
+                f,
+                "Operation too slow. Less than {} bytes/sec transferred the last {} seconds.",
+                limit,
+                time.as_secs()
+            ),
+            Error::Timeout(message) => write!(f, "Operation timed out: {}", message),
+            Error::Transfer(message) => write!(f, "Failure receiving data: {}", message),
+            Error::Write(message) => write!(f, "Failed writing body: {}", message),
+            Error::Netrc(message) => write!(f, "Failed to use netrc file: {}", message),
+            Error::TlsHandshake(message) => write!(f, "TLS connect error: {}", message),
+            Error::Certificate(message) => {
+                write!(f, "Problem with the local client certificate: {}", message)
+            }
+            Error::PeerVerification(message) => write!(
+                f,
+                "The server certificate could not be verified: {}",
+                message
+            ),
+            Error::CaCertificate(message) => {
+                write!(f, "Problem with the CA certificates: {}", message)
+            }
+            Error::PinnedPublicKey(message) => write!(f, "{}{}", PIN_MISMATCH, message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+const PIN_MISMATCH: &str = "The server public key does not match the pinned key: ";
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            Error::Timeout(e.to_string())
+        } else if e.is_connect() {
+            // The TLS backends only report what went wrong in their messages.
+            let mut causes = String::new();
+            let mut source = std::error::Error::source(&e);
+            while let Some(cause) = source {
+                let message = cause.to_string();
+                // The pinning verifier passes on the message of its own error.
+                if let Some((_, detail)) = message.split_once(PIN_MISMATCH) {
+                    return Error::PinnedPublicKey(detail.to_string());
+                }
+                causes.push_str(&message);
+                source = cause.source();
+            }
+            if causes.contains("certificate verify failed")
+                || causes.contains("invalid peer certificate")
+            {
+                Error::PeerVerification(e.to_string())
+            } else if causes.contains("SSL routines") || causes.contains("handshake") {
+                Error::TlsHandshake(e.to_string())
+            } else {
+                Error::CouldNotConnect(e.to_string())
+            }
+        } else if e.is_builder() {
+            Error::BadInput(e.to_string())
+        } else {
+            Error::Transfer(e.to_string())
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::path::PathBuf;
+
+use reqwest::blocking::multipart::{Form, Part};
+
+use crate::error::Error;
+
+/// One `-F` argument.
+#[derive(Debug, Clone)]
+pub struct FormPart {
+    raw: String,
+    name: String,
+    value: FormValue,
+    content_type: Option<String>,
+    filename: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+enum FormValue {
+    /// `name=value`
+    Text(String),
+    /// `name=@path`: the file is uploaded as an attachment.
+    File(PathBuf),
+    /// `name=<path`: the file's content becomes a text field.
+    TextFile(PathBuf),
+}
+
+impl FormPart {
+    /// Parses `name=value`, `name=@path[;type=mime][;filename=name]` or
+    /// `name=<path[;type=mime]`.
+    pub fn parse(arg: &str) -> Result<Self, Error> {
+        let invalid = || Error::BadInput(format!("Invalid form field: {}", arg));
+        let (name, rest) = arg.split_once('=').ok_or_else(invalid)?;
+        if name.is_empty() {
+            return Err(invalid());
+        }
+        let mut part = FormPart {
+            raw: arg.to_string(),
+            name: name.to_string(),
+            value: FormValue::Text(rest.to_string()),
+            content_type: None,
+            filename: None,
+        };
+        if let Some(source) = rest.strip_prefix('@').or_else(|| rest.strip_prefix('<')) {
+            let mut params = source.split(';');
+            let path = PathBuf::from(params.next().unwrap_or_default());
+            part.value = if rest.starts_with('@') {
+                FormValue::File(path)
+            } else {
+                FormValue::TextFile(path)
+            };
+            for param in params {
+                match param.trim().split_once('=') {
+                    Some(("type", mime)) => part.content_type = Some(mime.to_string()),
+                    Some(("filename", filename)) => {
+                        part.filename = Some(filename.trim_matches('"').to_string())
+                    }
+                    _ => return Err(invalid()),
+                }
+            }
+        }
+        Ok(part)
+    }
+
+    /// The argument as it was given on the command line.
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+}
+
+/// Builds a fresh multipart body; files are reopened on every call so the
+/// body can be sent again after a redirect.
+pub(crate) fn build_form(parts: &[FormPart]) -> Result<Form, Error> {
+    let mut form = Form::new();
+    for part in parts {
+        let read_error = |path: &PathBuf, e: std::io::Error| {
+            Error::BadInput(format!("Failed to read {}: {}", path.display(), e))
+        };
+        let mut body = match &part.value {
+            FormValue::Text(text) => Part::text(text.clone()),
+            FormValue::File(path) => Part::file(path).map_err(|e| read_error(path, e))?,
+            FormValue::TextFile(path) => {
+                Part::text(std::fs::read_to_string(path).map_err(|e| read_error(path, e))?)
+            }
+        };
+        if let Some(content_type) = &part.content_type {
+            body = body
+                .mime_str(content_type)
+                .map_err(|_| Error::BadInput(format!("Invalid content type: {}", content_type)))?;
+        }
+        if let Some(filename) = &part.filename {
+            body = body.file_name(filename.clone());
+        }
+        form = form.part(part.name.clone(), body);
+    }
+    Ok(form)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_text_field() {
+        let part = FormPart::parse("name=a=b;c").unwrap();
+        assert_eq!(part.name, "name");
+        assert!(matches!(&part.value, FormValue::Text(text) if text == "a=b;c"));
+        assert_eq!(part.as_str(), "name=a=b;c");
+    }
+
+    #[test]
+    fn parses_file_with_params() {
+        let part =
+            FormPart::parse("doc=@report.pdf;type=application/pdf;filename=\"r.pdf\"").unwrap();
+        assert!(
+            matches!(&part.value, FormValue::File(path) if path == &PathBuf::from("report.pdf"))
+        );
+        assert_eq!(part.content_type.as_deref(), Some("application/pdf"));
+        assert_eq!(part.filename.as_deref(), Some("r.pdf"));
+    }
+
+    #[test]
+    fn parses_text_file() {
+        let part = FormPart::parse("notes=<notes.txt;type=text/plain").unwrap();
+        assert!(
+            matches!(&part.value, FormValue::TextFile(path) if path == &PathBuf::from("notes.txt"))
+        );
+        assert_eq!(part.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(part.filename, None);
+    }
+
+    #[test]
+    fn rejects_invalid_fields() {
+        assert!(FormPart::parse("novalue").is_err());
+        assert!(FormPart::parse("=value").is_err());
+        assert!(FormPart::parse("file=@a.txt;unknown=1").is_err());
+    }
+}
@@ -0,0 +1,92 @@
+use reqwest::header::{HeaderName, HeaderValue};
+
+use crate::error::Error;
+
+/// A single `-H` argument.
+#[derive(Debug, Clone)]
+pub enum HeaderArg {
+    /// Replace any default header of this name, then send the value.
+    Set(HeaderName, HeaderValue),
+    /// Do not send this header at all.
+    Remove(HeaderName),
+}
+
+/// Parses `-H` arguments, expanding `@file` into one header per line.
+pub fn parse_header_args(args: &[String]) -> Result<Vec<HeaderArg>, Error> {
+    let mut headers = Vec::new();
+    for arg in args {
+        if let Some(path) = arg.strip_prefix('@') {
+            let contents = std::fs::read_to_string(path).map_err(|e| {
+                Error::BadInput(format!("Failed to read headers from {}: {}", path, e))
+            })?;
+            for line in contents.lines() {
+                if !line.trim().is_empty() {
+                    headers.push(parse_header_line(line)?);
+                }
+            }
+        } else {
+            headers.push(parse_header_line(arg)?);
+        }
+    }
+    Ok(headers)
+}
+
+/// Parses one header line: "Name: value" sets a header, "Name:" removes it
+/// and "Name;" sends it with an empty value.
+pub fn parse_header_line(line: &str) -> Result<HeaderArg, Error> {
+    let line = line.trim_end_matches(['\r', '\n']);
+    let invalid = || Error::BadInput(format!("Invalid header: {}", line));
+    if let Some((name, value)) = line.split_once(':') {
+        let name = HeaderName::from_bytes(name.trim().as_bytes()).map_err(|_| invalid())?;
+        let value = value.trim();
+        if value.is_empty() {
+            return Ok(HeaderArg::Remove(name));
+        }
+        let value = HeaderValue::from_str(value).map_err(|_| invalid())?;
+        Ok(HeaderArg::Set(name, value))
+    } else if let Some(name) = line.strip_suffix(';') {
+        let name = HeaderName::from_bytes(name.trim().as_bytes()).map_err(|_| invalid())?;
+        Ok(HeaderArg::Set(name, HeaderValue::from_static("")))
+    } else {
+        Err(invalid())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sets_trimmed_value() {
+        match parse_header_line("X-Test:  hello world \r\n").unwrap() {
+            HeaderArg::Set(name, value) => {
+                assert_eq!(name, "x-test");
+                assert_eq!(value, "hello world");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn colon_without_value_removes() {
+        assert!(matches!(
+            parse_header_line("Accept:").unwrap(),
+            HeaderArg::Remove(name) if name == "accept"
+        ));
+    }
+
+    #[test]
+    fn semicolon_sends_empty_value() {
+        assert!(matches!(
+            parse_header_line("X-Empty;").unwrap(),
+            HeaderArg::Set(name, value) if name == "x-empty" && value.is_empty()
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert!(parse_header_line("no separator").is_err());
+        assert!(parse_header_line("Bad Name: value").is_err());
+        assert!(parse_header_line("X-Test: bad\u{7f}value").is_err());
+    }
+}
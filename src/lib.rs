@@ -0,0 +1,38 @@
+//! The request/response engine behind the `curl` binary.
+//!
+//! Build a [`RequestSpec`], hand it to [`execute`] and render the resulting
+//! [`Outcome`] with the functions in [`output`].
+
+mod auth;
+mod client;
+mod cookie;
+mod data;
+mod error;
+mod form;
+mod headers;
+mod netrc;
+pub mod output;
+mod probe;
+mod proxy;
+mod request;
+mod resolve;
+mod retry;
+mod tls;
+mod upload;
+mod verbose;
+
+pub use auth::{Auth, AuthScheme, Credentials};
+pub use client::{execute, Outcome};
+pub use cookie::CookieJar;
+pub use data::{join_data, DataArg};
+pub use error::Error;
+pub use form::FormPart;
+pub use headers::{parse_header_args, parse_header_line, HeaderArg};
+pub use netrc::Netrc;
+pub use probe::{connect_all, Probe, ProbeResponse};
+pub use proxy::{NoProxy, ProxySettings};
+pub use request::{Body, RequestSpec};
+pub use resolve::{ConnectTo, IpVersion, ResolveOverride};
+pub use retry::RetryPolicy;
+pub use tls::{CertKind, ClientCert, PublicKeyPin, TlsOptions, TlsVersion};
+pub use upload::{upload_size, upload_url};
@@ -1,126 +1,655 @@
-use std::net::ToSocketAddrs;
-use reqwest::Error;
-use url::Url;
-use serde_json;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use curl::output::{
+    format_headers, format_preamble, format_probes, format_write_out, open_output, remote_name,
+    write_body, JsonFormat,
+};
+use curl::{
+    connect_all, execute, join_data, parse_header_args, upload_url, Auth, AuthScheme, Body,
+    CertKind, ClientCert, ConnectTo, CookieJar, Credentials, DataArg, Error, FormPart, IpVersion,
+    Netrc, NoProxy, ProxySettings, PublicKeyPin, RequestSpec, ResolveOverride, RetryPolicy,
+    TlsOptions, TlsVersion,
+};
+use structopt::clap::ArgMatches;
 use structopt::StructOpt;
 
-fn main() -> Result<(), Error> {
-    #[derive(StructOpt, Debug)]
-    #[structopt(name = "curl")]
-    struct Opt {
-        #[structopt(name = "URL")]
-        url: String,
+#[derive(StructOpt, Debug)]
+#[structopt(name = "curl")]
+struct Opt {
+    #[structopt(name = "URL")]
+    url: String,
 
-        #[structopt(short = "X", long = "request", default_value = "GET")]
-        method: String,
+    #[structopt(short = "X", long = "request")]
+    method: Option<String>,
 
-        #[structopt(short = "d", long = "data")]
-        data: Option<String>,
+    /// Send data as-is; repeat to join with '&', or use @file / @- for stdin
+    #[structopt(short = "d", long = "data", number_of_values = 1)]
+    data: Vec<String>,
 
-        #[structopt(long = "json")]
-        json: Option<String>,
-    }
+    /// Percent-encode and send content, name=content, @file or name@file
+    #[structopt(long = "data-urlencode", number_of_values = 1)]
+    data_urlencode: Vec<String>,
 
-    let opt = Opt::from_args();
+    /// Add a multipart field: name=value, name=@file[;type=mime][;filename=name] or name=<file
+    #[structopt(
+        short = "F",
+        long = "form",
+        number_of_values = 1,
+        conflicts_with_all = &["data", "data-urlencode", "json"]
+    )]
+    form: Vec<String>,
 
-    let url = &opt.url;
-    let method = if opt.json.is_some() { "POST" } else { &opt.method };
-    let data = opt.data.as_deref();
-    let json: Option<&str> = opt.json.as_deref();
+    /// Stream a file as a PUT body; '-' reads stdin
+    #[structopt(
+        short = "T",
+        long = "upload-file",
+        parse(from_os_str),
+        conflicts_with_all = &["data", "data-urlencode", "form", "json"]
+    )]
+    upload_file: Option<PathBuf>,
 
-    println!("Requesting URL: {}", url);
-    println!("Method: {}", method);
-    if let Some(data) = data {
-        println!("Data: {}", data);
-    }
-    if let Some(json_data) = json {
-        println!("JSON: {}", json_data);
+    #[structopt(long = "json", conflicts_with_all = &["data", "data-urlencode"])]
+    json: Option<String>,
+
+    #[structopt(short = "H", long = "header", number_of_values = 1)]
+    headers: Vec<String>,
+
+    /// Exit with code 22 and no output on HTTP errors (4xx and 5xx)
+    #[structopt(short = "f", long = "fail", conflicts_with = "fail-with-body")]
+    fail: bool,
+
+    /// Exit with code 22 on HTTP errors after printing the body
+    #[structopt(long = "fail-with-body")]
+    fail_with_body: bool,
+
+    /// Include the response status line and headers in the output
+    #[structopt(short = "i", long = "include")]
+    include: bool,
+
+    /// Send a HEAD request and print only the response headers
+    #[structopt(short = "I", long = "head", conflicts_with_all = &["data", "data-urlencode", "form", "json", "method", "upload-file"])]
+    head: bool,
+
+    /// Write the response body to this file instead of stdout; '-' means stdout
+    #[structopt(short = "o", long = "output", parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    /// Write the response body to a file named after the last URL path segment
+    #[structopt(short = "O", long = "remote-name", conflicts_with = "output")]
+    remote_name: bool,
+
+    /// Create missing parent directories of the -o/-O file
+    #[structopt(long = "create-dirs")]
+    create_dirs: bool,
+
+    /// Overwrite an existing file given to -o/-O
+    #[structopt(long = "clobber")]
+    clobber: bool,
+
+    #[structopt(short = "L", long = "location")]
+    location: bool,
+
+    /// Maximum number of redirects to follow with -L; -1 means unlimited
+    #[structopt(long = "max-redirs", default_value = "50", allow_hyphen_values = true)]
+    max_redirs: i64,
+
+    /// Trace the request and response exchange on stderr
+    #[structopt(short = "v", long = "verbose")]
+    verbose: bool,
+
+    /// Do not print the request summary before the response
+    #[structopt(short = "s", long = "silent")]
+    silent: bool,
+
+    /// Send the request to every resolved address and report each response
+    #[structopt(long = "connect-all")]
+    connect_all: bool,
+
+    /// Use fixed addresses for a host and port: host:port:addr[,addr]...
+    #[structopt(long = "resolve", number_of_values = 1)]
+    resolve: Vec<String>,
+
+    /// Connect to host2:port2 whenever the URL names host1:port1
+    #[structopt(long = "connect-to", number_of_values = 1)]
+    connect_to: Vec<String>,
+
+    /// Resolve and connect over IPv4 only
+    #[structopt(short = "4", long = "ipv4", conflicts_with = "ipv6")]
+    ipv4: bool,
+
+    /// Resolve and connect over IPv6 only
+    #[structopt(short = "6", long = "ipv6")]
+    ipv6: bool,
+
+    /// Send from this local address or network interface
+    #[structopt(long = "interface")]
+    interface: Option<String>,
+
+    /// Send from this local port or range of ports (N or N-M); not supported, always an error
+    #[structopt(long = "local-port")]
+    local_port: Option<String>,
+
+    /// Seconds to wait for the connection to be established
+    #[structopt(long = "connect-timeout", parse(try_from_str = parse_seconds))]
+    connect_timeout: Option<Duration>,
+
+    /// Seconds the whole transfer may take
+    #[structopt(short = "m", long = "max-time", parse(try_from_str = parse_seconds))]
+    max_time: Option<Duration>,
+
+    /// Abort when fewer bytes per second than this arrive for --speed-time (0 disables)
+    #[structopt(short = "Y", long = "speed-limit")]
+    speed_limit: Option<u64>,
+
+    /// Seconds the transfer may stay below --speed-limit (default 30, 0 disables)
+    #[structopt(short = "y", long = "speed-time", parse(try_from_str = parse_seconds))]
+    speed_time: Option<Duration>,
+
+    /// Retry transient failures up to this many times
+    #[structopt(long = "retry", default_value = "0")]
+    retry: u32,
+
+    /// Seconds to wait between retries instead of backing off exponentially
+    #[structopt(long = "retry-delay", parse(try_from_str = parse_seconds))]
+    retry_delay: Option<Duration>,
+
+    /// Seconds after which no further retry is started
+    #[structopt(long = "retry-max-time", parse(try_from_str = parse_seconds))]
+    retry_max_time: Option<Duration>,
+
+    /// Retry on any error, not only transient ones
+    #[structopt(long = "retry-all-errors")]
+    retry_all_errors: bool,
+
+    /// Server credentials as user:password; the password is prompted for if omitted
+    #[structopt(short = "u", long = "user")]
+    user: Option<String>,
+
+    /// Use HTTP Basic authentication (the default with -u and netrc)
+    #[structopt(long = "basic", conflicts_with_all = &["digest", "anyauth"])]
+    basic: bool,
+
+    /// Use HTTP Digest authentication
+    #[structopt(long = "digest", conflicts_with = "anyauth")]
+    digest: bool,
+
+    /// Pick the strongest authentication the server offers
+    #[structopt(long = "anyauth")]
+    anyauth: bool,
+
+    /// Send an OAuth 2 bearer token
+    #[structopt(long = "oauth2-bearer", conflicts_with = "user")]
+    oauth2_bearer: Option<String>,
+
+    /// Read credentials for the host from ~/.netrc
+    #[structopt(short = "n", long = "netrc", conflicts_with = "netrc-optional")]
+    netrc: bool,
+
+    /// Like --netrc, but ignore a missing ~/.netrc
+    #[structopt(long = "netrc-optional")]
+    netrc_optional: bool,
+
+    /// Read credentials for the host from this netrc file
+    #[structopt(long = "netrc-file", parse(from_os_str))]
+    netrc_file: Option<PathBuf>,
+
+    /// Send cookies: "name=value; name2=value2", or a Netscape cookie file to read
+    #[structopt(short = "b", long = "cookie", number_of_values = 1)]
+    cookie: Vec<String>,
+
+    /// Write all cookies to this file in Netscape format after the transfer
+    #[structopt(short = "c", long = "cookie-jar", parse(from_os_str))]
+    cookie_jar: Option<PathBuf>,
+
+    /// Discard session cookies read with -b, starting a new session
+    #[structopt(short = "j", long = "junk-session-cookies")]
+    junk_session_cookies: bool,
+
+    /// Do not verify the server certificate (unsafe)
+    #[structopt(short = "k", long = "insecure")]
+    insecure: bool,
+
+    /// Trust only the CA certificates in this PEM bundle
+    #[structopt(long = "cacert", parse(from_os_str))]
+    cacert: Option<PathBuf>,
+
+    /// Trust only the CA certificates in this directory
+    #[structopt(long = "capath", parse(from_os_str))]
+    capath: Option<PathBuf>,
+
+    /// Client certificate for mutual TLS, as file[:password]
+    #[structopt(short = "E", long = "cert")]
+    cert: Option<String>,
+
+    /// Client certificate type: PEM or P12 (default from the file extension)
+    #[structopt(long = "cert-type", possible_values = &["PEM", "P12"], case_insensitive = true)]
+    cert_type: Option<String>,
+
+    /// PKCS#8 private key for a PEM client certificate
+    #[structopt(long = "key", parse(from_os_str))]
+    key: Option<PathBuf>,
+
+    /// Require at least TLS 1.0
+    #[structopt(long = "tlsv1.0")]
+    tlsv1_0: bool,
+
+    /// Require at least TLS 1.1
+    #[structopt(long = "tlsv1.1")]
+    tlsv1_1: bool,
+
+    /// Require at least TLS 1.2
+    #[structopt(long = "tlsv1.2")]
+    tlsv1_2: bool,
+
+    /// Require at least TLS 1.3
+    #[structopt(long = "tlsv1.3")]
+    tlsv1_3: bool,
+
+    /// Highest TLS version to use: 1.0, 1.1, 1.2 or 1.3
+    #[structopt(long = "tls-max")]
+    tls_max: Option<String>,
+
+    /// Public key the server must present: sha256//<base64>[;sha256//...] or a key file
+    #[structopt(long = "pinnedpubkey")]
+    pinned_pubkey: Option<String>,
+
+    /// Send requests through this proxy: [scheme://][user:password@]host[:port]
+    #[structopt(short = "x", long = "proxy")]
+    proxy: Option<String>,
+
+    /// Proxy credentials as user:password; the password is prompted for if omitted
+    #[structopt(short = "U", long = "proxy-user")]
+    proxy_user: Option<String>,
+
+    /// Comma-separated hosts, domains and CIDR ranges that bypass the proxy
+    #[structopt(long = "noproxy")]
+    noproxy: Option<String>,
+
+    /// Print this template after the transfer, e.g. '%{http_code} %{time_total}\n'; @file reads it
+    #[structopt(short = "w", long = "write-out")]
+    write_out: Option<String>,
+
+    /// Largest JSON body, in bytes, that is pretty-printed instead of streamed
+    #[structopt(long = "max-json-size", default_value = "8388608")]
+    max_json_size: usize,
+
+    /// Keep JSON object keys in the order the server sent them
+    #[structopt(long = "no-sort-keys")]
+    no_sort_keys: bool,
+
+    /// Spaces per nesting level when pretty-printing JSON
+    #[structopt(long = "indent", default_value = "2")]
+    indent: usize,
+
+    /// Print JSON bodies on a single line
+    #[structopt(long = "compact", conflicts_with = "indent")]
+    compact: bool,
+}
+
+fn main() {
+    let matches = Opt::clap().get_matches();
+    let opt = Opt::from_clap(&matches);
+    if let Err(e) = run(opt, &matches) {
+        eprintln!("Error: {}", e);
+        std::process::exit(e.exit_code());
     }
+}
 
-    if (!url.starts_with("http://")) && (!url.starts_with("https://")) {
-        println!("Error: The URL does not have a valid base protocol.");
-        std::process::exit(1);
+fn run(opt: Opt, matches: &ArgMatches) -> Result<(), Error> {
+    let headers = parse_header_args(&opt.headers)?;
+    let netrc = netrc(&opt)?;
+    let tls = tls_options(&opt)?;
+    let proxy = proxy_settings(&opt)?;
+    let write_out = opt
+        .write_out
+        .as_deref()
+        .map(write_out_template)
+        .transpose()?;
+
+    let output = if opt.remote_name {
+        Some(remote_name(&opt.url)?)
+    } else {
+        opt.output
+    };
+
+    let form_data = if opt.data.is_empty() && opt.data_urlencode.is_empty() {
+        None
+    } else {
+        Some(join_data(&data_args(
+            &opt.data,
+            &opt.data_urlencode,
+            matches,
+        ))?)
+    };
+
+    let url = match &opt.upload_file {
+        Some(path) => upload_url(&opt.url, path),
+        None => opt.url,
+    };
+
+    let resolve = opt
+        .resolve
+        .iter()
+        .map(|arg| ResolveOverride::parse(arg))
+        .collect::<Result<Vec<_>, _>>()?;
+    let connect_to = opt
+        .connect_to
+        .iter()
+        .map(|arg| ConnectTo::parse(arg))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if let Some(local_port) = &opt.local_port {
+        return Err(Error::Unsupported(format!(
+            "--local-port {}: the HTTP client cannot choose its source port.",
+            local_port
+        )));
     }
 
-    let parsed_url = match Url::parse(url) {
-        Ok(url) => url,
-        Err(e) => {
-            if e.to_string().contains("invalid IPv6 address") {
-                println!("Error: The URL contains an invalid IPv6 address.");
-            } else if e.to_string().contains("invalid IPv4 address") {
-                println!("Error: The URL contains an invalid IPv4 address.");
-            } else if e.to_string().contains("invalid port number") {
-                println!("Error: The URL contains an invalid port number.");
+    let mut spec = RequestSpec::new(url)
+        .with_headers(headers)
+        .with_follow_redirects(opt.location)
+        .with_max_redirects(u32::try_from(opt.max_redirs).ok())
+        .with_verbose(opt.verbose)
+        .with_resolve(resolve)
+        .with_connect_to(connect_to)
+        .with_ip_version(if opt.ipv4 {
+            IpVersion::V4
+        } else if opt.ipv6 {
+            IpVersion::V6
+        } else {
+            IpVersion::Any
+        });
+    if let Some(interface) = opt.interface {
+        spec = spec.with_interface(interface);
+    }
+    if let Some(timeout) = opt.connect_timeout {
+        spec = spec.with_connect_timeout(timeout);
+    }
+    if let Some(max_time) = opt.max_time {
+        spec = spec.with_max_time(max_time);
+    }
+    // The scheme also applies to credentials found in netrc.
+    spec = spec.with_auth_scheme(if opt.basic || !(opt.digest || opt.anyauth) {
+        AuthScheme::Basic
+    } else if opt.digest {
+        AuthScheme::Digest
+    } else {
+        AuthScheme::Any
+    });
+    if let Some(user) = &opt.user {
+        spec = spec.with_auth(Auth::Credentials(credentials(user, "host")?));
+    } else if let Some(token) = opt.oauth2_bearer {
+        spec = spec.with_auth(Auth::Bearer(token));
+    }
+    if let Some(netrc) = netrc {
+        spec = spec.with_netrc(netrc);
+    }
+    if !opt.cookie.is_empty() || opt.cookie_jar.is_some() {
+        let mut jar = CookieJar::new();
+        for arg in &opt.cookie {
+            if arg.contains('=') {
+                jar.add_pairs(arg);
             } else {
-                println!("Error: {}", e);
+                jar.load(Path::new(arg), !opt.junk_session_cookies)?;
             }
-            std::process::exit(1);
         }
-    };
-
-    if let Some(host) = parsed_url.host_str() {
-        let port = parsed_url.port().unwrap_or(80);
-        let addr = format!("{}:{}", host, port);
-        if addr.to_socket_addrs().is_err() {
-            println!("Error: Unable to connect to the server. Perhaps the network is offline or the server hostname cannot be resolved.");
-            std::process::exit(1);
+        spec = spec.with_cookies(jar);
+    }
+    spec = spec.with_tls(tls).with_proxy(proxy);
+    if opt.retry > 0 {
+        spec = spec.with_retry(RetryPolicy {
+            retries: opt.retry,
+            delay: opt.retry_delay.filter(|delay| !delay.is_zero()),
+            max_time: opt.retry_max_time.filter(|max_time| !max_time.is_zero()),
+            all_errors: opt.retry_all_errors,
+        });
+    }
+    // A zero limit or time turns the check off, as in curl.
+    if opt.speed_limit.is_some() || opt.speed_time.is_some() {
+        let speed_limit = opt.speed_limit.unwrap_or(1);
+        let speed_time = opt.speed_time.unwrap_or(Duration::from_secs(30));
+        if speed_limit > 0 && !speed_time.is_zero() {
+            spec = spec.with_speed_limit(speed_limit, speed_time);
         }
     }
+    if opt.head {
+        spec = spec.with_method("HEAD");
+    } else if let Some(method) = opt.method {
+        spec = spec.with_method(method);
+    }
+    if let Some(json_data) = opt.json {
+        spec = spec.with_body(Body::Json(json_data));
+    } else if let Some(data) = form_data {
+        spec = spec.with_body(Body::Form(data));
+    } else if !opt.form.is_empty() {
+        let parts = opt
+            .form
+            .iter()
+            .map(|arg| FormPart::parse(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        spec = spec.with_body(Body::Multipart(parts));
+    } else if let Some(path) = opt.upload_file {
+        spec = spec.with_body(Body::Upload(path));
+    }
+
+    if !opt.silent {
+        print!("{}", format_preamble(&spec)?);
+    }
 
-    let client = reqwest::blocking::Client::new();
-    let response = if let Some(json_data) = json {
-        match serde_json::from_str::<serde_json::Value>(json_data) {
-            Ok(_) => (),
-            Err(e) => {
-                panic!("Invalid JSON: Error(\"{}\")", e);
+    if opt.connect_all {
+        let probes = connect_all(&spec)?;
+        print!("{}", format_probes(&probes));
+        return match probes.into_iter().find_map(|probe| probe.result.err()) {
+            Some(e) => Err(e),
+            None => Ok(()),
+        };
+    }
+
+    // Opened before the request goes out, so that refusing to overwrite a
+    // file leaves the server untouched. `-o -` still writes the body
+    // unformatted, just to stdout.
+    let stdout = std::io::stdout();
+    let mut out: Box<dyn Write> = match output.as_deref().filter(|path| *path != Path::new("-")) {
+        Some(path) => Box::new(open_output(path, opt.create_dirs, opt.clobber)?),
+        None => Box::new(stdout.lock()),
+    };
+    let mut outcome = execute(&spec)?;
+    if let (Some(path), Some(jar)) = (&opt.cookie_jar, &outcome.cookies) {
+        jar.save(path)?;
+    }
+
+    if opt.fail {
+        if let Err(e) = outcome.error_for_status() {
+            if let Some(template) = &write_out {
+                print!("{}", format_write_out(template, &outcome));
             }
+            return Err(e);
         }
-        client.post(url)
-            .header("Content-Type", "application/json")
-            .body(json_data.to_string())
-            .send()?
-    } else if method == "POST" {
-        if let Some(data) = data {
-            let mut data_to_post: Vec<(&str, &str)> = Vec::new();
-            for pair in data.split("&") {
-                let mut key_value = pair.split("=");
-                let key = key_value.next().unwrap();
-                let value = key_value.next().unwrap();
-                data_to_post.push((key, value));
-            }
-            client.post(url).form(&data_to_post).send()?
+    }
+
+    if opt.include || opt.head {
+        out.write_all(format_headers(&outcome).as_bytes())
+            .map_err(|e| Error::Write(e.to_string()))?;
+    }
+    if !opt.head {
+        if output.is_some() {
+            outcome.copy_to(&mut out)?;
         } else {
-            println!("Error: POST method requires data to be specified with -d.");
-            std::process::exit(1);
+            write_body(
+                &mut outcome,
+                &mut out,
+                opt.max_json_size,
+                &JsonFormat {
+                    sort_keys: !opt.no_sort_keys,
+                    indent: Some(opt.indent).filter(|_| !opt.compact),
+                },
+                !opt.silent,
+            )?;
         }
-    } else {
-        client.get(url).send()?
-    };
+    }
+    out.flush().map_err(|e| Error::Write(e.to_string()))?;
+    drop(out);
+    if let Some(template) = &write_out {
+        print!("{}", format_write_out(template, &outcome));
+    }
 
-    if !response.status().is_success() {
-        println!("Error: Request failed with status code: {}.", response.status().as_u16());
-        std::process::exit(1);
+    if opt.fail_with_body {
+        outcome.error_for_status()?;
     }
+    Ok(())
+}
 
-    let body: String = response.text()?;
-    if let Ok(json) = serde_json::from_str::<serde_json::Value>(&body) {
-        let mut sorted_json = serde_json::Map::new();
-        if let serde_json::Value::Object(map) = json {
-            let mut keys: Vec<_> = map.keys().collect();
-            keys.sort();
-            for key in keys {
-                sorted_json.insert(key.clone(), map[key].clone());
-            }
+/// Interleaves `-d` and `--data-urlencode` in the order they were given.
+fn data_args(data: &[String], data_urlencode: &[String], matches: &ArgMatches) -> Vec<DataArg> {
+    let raw = matches
+        .indices_of("data")
+        .into_iter()
+        .flatten()
+        .zip(data.iter().map(|value| DataArg::Raw(value.clone())));
+    let encoded = matches
+        .indices_of("data-urlencode")
+        .into_iter()
+        .flatten()
+        .zip(
+            data_urlencode
+                .iter()
+                .map(|value| DataArg::UrlEncode(value.clone())),
+        );
+    let mut args: Vec<(usize, DataArg)> = raw.chain(encoded).collect();
+    args.sort_by_key(|(index, _)| *index);
+    args.into_iter().map(|(_, arg)| arg).collect()
+}
+
+fn parse_seconds(input: &str) -> Result<Duration, String> {
+    input
+        .parse::<f64>()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| format!("invalid number of seconds: {}", input))
+}
+
+/// Splits `-u user:password`, prompting for the password when it is
+/// missing and stdin is a terminal.
+fn credentials(user: &str, what: &str) -> Result<Credentials, Error> {
+    if let Some((user, password)) = user.split_once(':') {
+        return Ok(Credentials::new(user, password));
+    }
+    if !std::io::stdin().is_terminal() {
+        return Ok(Credentials::new(user, ""));
+    }
+    let password =
+        rpassword::prompt_password(format!("Enter {} password for user '{}':", what, user))
+            .map_err(|e| Error::BadInput(format!("Failed to read password: {}", e)))?;
+    Ok(Credentials::new(user, password))
+}
+
+/// The netrc file selected by `-n`, `--netrc-optional` or `--netrc-file`.
+fn netrc(opt: &Opt) -> Result<Option<Netrc>, Error> {
+    if let Some(path) = &opt.netrc_file {
+        return Netrc::from_file(path).map(Some);
+    }
+    if !opt.netrc && !opt.netrc_optional {
+        return Ok(None);
+    }
+    let path = Netrc::default_path()
+        .ok_or_else(|| Error::Netrc("cannot find the home directory".to_string()))?;
+    if opt.netrc_optional && !path.exists() {
+        return Ok(None);
+    }
+    Netrc::from_file(&path).map(Some)
+}
+
+/// Collects the TLS options, warning loudly when verification is off.
+fn tls_options(opt: &Opt) -> Result<TlsOptions, Error> {
+    if opt.insecure {
+        eprintln!(
+            "WARNING: --insecure is set: the server certificate is not verified and the connection can be intercepted."
+        );
+    }
+    let client_cert = opt.cert.as_ref().map(|cert| {
+        let (path, password) = match cert.split_once(':') {
+            Some((path, password)) => (path, Some(password.to_string())),
+            None => (cert.as_str(), None),
+        };
+        let path = PathBuf::from(path);
+        let kind = match opt.cert_type.as_deref() {
+            Some(kind) if kind.eq_ignore_ascii_case("P12") => CertKind::Pkcs12,
+            Some(_) => CertKind::Pem,
+            None => match path.extension().and_then(|extension| extension.to_str()) {
+                Some("p12" | "pfx") => CertKind::Pkcs12,
+                _ => CertKind::Pem,
+            },
+        };
+        ClientCert {
+            path,
+            key: opt.key.clone(),
+            password,
+            kind,
         }
-        let pretty_json = serde_json::to_string_pretty(&sorted_json).map_err(|e| {
-            println!("Error: Failed to format JSON: {}", e);
-            std::process::exit(1);
-        })?;
-        println!("Response body (JSON with sorted keys):\n{}", pretty_json);
+    });
+    let min_version = if opt.tlsv1_3 {
+        Some(TlsVersion::Tls1_3)
+    } else if opt.tlsv1_2 {
+        Some(TlsVersion::Tls1_2)
+    } else if opt.tlsv1_1 {
+        Some(TlsVersion::Tls1_1)
+    } else if opt.tlsv1_0 {
+        Some(TlsVersion::Tls1_0)
     } else {
-        let trimmed_body = body.trim_end();
-        println!("Response body:\n{}", trimmed_body);
+        None
+    };
+    Ok(TlsOptions {
+        insecure: opt.insecure,
+        ca_cert: opt.cacert.clone(),
+        ca_path: opt.capath.clone(),
+        client_cert,
+        min_version,
+        max_version: opt.tls_max.as_deref().map(TlsVersion::parse).transpose()?,
+        pinned_public_key: opt
+            .pinned_pubkey
+            .as_deref()
+            .map(PublicKeyPin::parse)
+            .transpose()?,
+    })
+}
+
+/// `-x` replaces the proxy environment variables; an empty `-x` disables
+/// proxies altogether.
+fn proxy_settings(opt: &Opt) -> Result<ProxySettings, Error> {
+    let mut settings = match opt.proxy.as_deref() {
+        Some("") => ProxySettings::default(),
+        Some(proxy) => ProxySettings::with_proxy(proxy)?,
+        None => ProxySettings::from_env()?,
+    };
+    if let Some(list) = &opt.noproxy {
+        settings.no_proxy = NoProxy::parse(list);
     }
+    if let Some(user) = &opt.proxy_user {
+        settings.credentials = Some(credentials(user, "proxy")?);
+    }
+    Ok(settings)
+}
 
-    Ok(())
+/// The `-w` template, read from a file or stdin when given as `@file` or
+/// `@-`.
+fn write_out_template(arg: &str) -> Result<String, Error> {
+    let path = match arg.strip_prefix('@') {
+        Some(path) => path,
+        None => return Ok(arg.to_string()),
+    };
+    let mut template = String::new();
+    let read = if path == "-" {
+        std::io::stdin().read_to_string(&mut template)
+    } else {
+        std::fs::File::open(path).and_then(|mut file| file.read_to_string(&mut template))
+    };
+    read.map_err(|e| {
+        Error::BadInput(format!(
+            "Failed to read write-out template from {}: {}",
+            path, e
+        ))
+    })?;
+    Ok(template)
 }
@@ -0,0 +1,234 @@
+use std::path::{Path, PathBuf};
+
+use crate::auth::Credentials;
+use crate::error::Error;
+
+/// The credentials listed in a `.netrc` file.
+#[derive(Debug, Clone, Default)]
+pub struct Netrc {
+    entries: Vec<Entry>,
+}
+
+/// One `machine` block, or the `default` block when `machine` is `None`.
+#[derive(Debug, Clone, Default)]
+struct Entry {
+    machine: Option<String>,
+    login: Option<String>,
+    password: Option<String>,
+}
+
+impl Netrc {
+    /// Parses the `machine`, `default`, `login`, `password`, `account` and
+    /// `macdef` tokens of a netrc file. Values may be double-quoted.
+    pub fn parse(contents: &str) -> Result<Self, Error> {
+        let mut tokens = Tokens { rest: contents };
+        let mut entries: Vec<Entry> = Vec::new();
+        while let Some(token) = tokens.next()? {
+            match token.as_str() {
+                "machine" => entries.push(Entry {
+                    machine: Some(tokens.value(&token)?),
+                    ..Entry::default()
+                }),
+                "default" => entries.push(Entry::default()),
+                "login" | "password" => {
+                    let value = tokens.value(&token)?;
+                    let entry = entries.last_mut().ok_or_else(|| {
+                        Error::Netrc(format!("'{}' outside of a machine block", token))
+                    })?;
+                    if token == "login" {
+                        entry.login = Some(value);
+                    } else {
+                        entry.password = Some(value);
+                    }
+                }
+                "account" => {
+                    tokens.value(&token)?;
+                }
+                "macdef" => {
+                    tokens.value(&token)?;
+                    tokens.skip_macro();
+                }
+                _ => return Err(Error::Netrc(format!("unexpected token '{}'", token))),
+            }
+        }
+        Ok(Netrc { entries })
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| Error::Netrc(format!("{}: {}", path.display(), e)))?;
+        Netrc::parse(&contents).map_err(|e| match e {
+            Error::Netrc(message) => Error::Netrc(format!("{}: {}", path.display(), message)),
+            e => e,
+        })
+    }
+
+    /// `~/.netrc`, if the home directory is known.
+    pub fn default_path() -> Option<PathBuf> {
+        std::env::var_os("HOME").map(|home| Path::new(&home).join(".netrc"))
+    }
+
+    /// The credentials for `host`, falling back to the `default` block.
+    /// With `user` set, only entries for that login match.
+    pub fn credentials(&self, host: &str, user: Option<&str>) -> Option<Credentials> {
+        let matches_user =
+            |entry: &&Entry| user.is_none_or(|user| entry.login.as_deref() == Some(user));
+        let entry = self
+            .entries
+            .iter()
+            .filter(matches_user)
+            .find(|entry| {
+                entry
+                    .machine
+                    .as_deref()
+                    .is_some_and(|machine| machine.eq_ignore_ascii_case(host))
+            })
+            .or_else(|| {
+                self.entries
+                    .iter()
+                    .filter(matches_user)
+                    .find(|entry| entry.machine.is_none())
+            })?;
+        if entry.login.is_none() && entry.password.is_none() {
+            return None;
+        }
+        Some(Credentials::new(
+            entry.login.as_deref().unwrap_or_default(),
+            entry.password.as_deref().unwrap_or_default(),
+        ))
+    }
+}
+
+struct Tokens<'a> {
+    rest: &'a str,
+}
+
+impl Tokens<'_> {
+    fn next(&mut self) -> Result<Option<String>, Error> {
+        loop {
+            self.rest = self.rest.trim_start();
+            if !self.rest.starts_with('#') {
+                break;
+            }
+            self.rest = self.rest.find('\n').map_or("", |end| &self.rest[end..]);
+        }
+        if self.rest.is_empty() {
+            return Ok(None);
+        }
+        if let Some(quoted) = self.rest.strip_prefix('"') {
+            let mut value = String::new();
+            let mut chars = quoted.char_indices();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '"' => {
+                        self.rest = &quoted[i + 1..];
+                        return Ok(Some(value));
+                    }
+                    '\\' => match chars.next() {
+                        Some((_, 'n')) => value.push('\n'),
+                        Some((_, 'r')) => value.push('\r'),
+                        Some((_, 't')) => value.push('\t'),
+                        Some((_, c)) => value.push(c),
+                        None => break,
+                    },
+                    c => value.push(c),
+                }
+            }
+            return Err(Error::Netrc("unterminated quoted string".to_string()));
+        }
+        let end = self
+            .rest
+            .find(char::is_whitespace)
+            .unwrap_or(self.rest.len());
+        let token = self.rest[..end].to_string();
+        self.rest = &self.rest[end..];
+        Ok(Some(token))
+    }
+
+    fn value(&mut self, keyword: &str) -> Result<String, Error> {
+        self.next()?
+            .ok_or_else(|| Error::Netrc(format!("missing value after '{}'", keyword)))
+    }
+
+    /// A macro body runs from the next line up to the first blank line.
+    fn skip_macro(&mut self) {
+        let mut lines = self.rest.split_inclusive('\n');
+        let mut skipped = lines.next().map_or(0, str::len);
+        for line in lines {
+            skipped += line.len();
+            if line.trim_end_matches(['\r', '\n']).is_empty() {
+                break;
+            }
+        }
+        self.rest = &self.rest[skipped..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credentials(netrc: &Netrc, host: &str, user: Option<&str>) -> Option<(String, String)> {
+        netrc.credentials(host, user).map(|credentials| {
+            (
+                credentials.user().to_string(),
+                credentials.password().to_string(),
+            )
+        })
+    }
+
+    #[test]
+    fn finds_machine_and_default_entries() {
+        let netrc = Netrc::parse(
+            "machine example.com login alice password a1\n\
+             # a comment\n\
+             machine example.com login bob password b2\n\
+             default login anonymous password guest\n",
+        )
+        .unwrap();
+        assert_eq!(
+            credentials(&netrc, "EXAMPLE.com", None),
+            Some(("alice".to_string(), "a1".to_string()))
+        );
+        assert_eq!(
+            credentials(&netrc, "example.com", Some("bob")),
+            Some(("bob".to_string(), "b2".to_string()))
+        );
+        assert_eq!(
+            credentials(&netrc, "other.org", None),
+            Some(("anonymous".to_string(), "guest".to_string()))
+        );
+        assert_eq!(credentials(&netrc, "other.org", Some("carol")), None);
+    }
+
+    #[test]
+    fn reads_quoted_values_and_skips_macros() {
+        let netrc = Netrc::parse(
+            "macdef init\ncd /pub\nmachine fake login x password y\n\n\
+             machine host account acct login \"a b\" password \"p\\\"w\\n\"\n",
+        )
+        .unwrap();
+        assert_eq!(credentials(&netrc, "fake", None), None);
+        assert_eq!(
+            credentials(&netrc, "host", None),
+            Some(("a b".to_string(), "p\"w\n".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        assert!(matches!(Netrc::parse("login alice"), Err(Error::Netrc(_))));
+        assert!(matches!(
+            Netrc::parse("machine host login"),
+            Err(Error::Netrc(_))
+        ));
+        assert!(matches!(
+            Netrc::parse("machine host password \"open"),
+            Err(Error::Netrc(_))
+        ));
+        assert!(matches!(
+            Netrc::parse("machine host port 21"),
+            Err(Error::Netrc(_))
+        ));
+    }
+}
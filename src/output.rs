@@ -0,0 +1,514 @@
+//! Rendering of requests and responses for the terminal.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use serde::Serialize;
+use url::Url;
+
+use crate::auth::redact_url;
+use crate::client::Outcome;
+use crate::error::Error;
+use crate::probe::Probe;
+use crate::request::{Body, RequestSpec};
+use crate::upload::upload_size;
+
+/// The "Requesting URL" block printed before a transfer.
+pub fn format_preamble(spec: &RequestSpec) -> Result<String, Error> {
+    let mut preamble = format!(
+        "Requesting URL: {}\nMethod: {}\n",
+        redact_url(spec.url()),
+        spec.method()
+    );
+    match spec.body() {
+        Some(Body::Form(data)) => preamble.push_str(&format!("Data: {}\n", data)),
+        Some(Body::Json(json_data)) => preamble.push_str(&format!("JSON: {}\n", json_data)),
+        Some(Body::Multipart(parts)) => {
+            for part in parts {
+                preamble.push_str(&format!("Form: {}\n", part.as_str()));
+            }
+        }
+        Some(Body::Upload(path)) => match upload_size(path)? {
+            Some(size) => {
+                preamble.push_str(&format!("Upload: {} ({} bytes)\n", path.display(), size))
+            }
+            None => preamble.push_str("Upload: stdin\n"),
+        },
+        None => {}
+    }
+    Ok(preamble)
+}
+
+/// How JSON response bodies are rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JsonFormat {
+    /// Sort object keys at every depth instead of keeping the server's
+    /// order.
+    pub sort_keys: bool,
+    /// Spaces per nesting level; `None` puts everything on one line.
+    pub indent: Option<usize>,
+}
+
+impl Default for JsonFormat {
+    fn default() -> Self {
+        JsonFormat {
+            sort_keys: true,
+            indent: Some(2),
+        }
+    }
+}
+
+/// Renders JSON bodies as `format` asks and passes anything else through
+/// with trailing whitespace removed. `label` heads the result with a
+/// "Response body" line naming how it was rendered.
+pub fn format_body(body: &str, format: &JsonFormat, label: bool) -> Result<String, Error> {
+    let json = match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(json) => json,
+        Err(_) if label => return Ok(format!("Response body:\n{}", body.trim_end())),
+        Err(_) => return Ok(body.trim_end().to_string()),
+    };
+    let json = if format.sort_keys {
+        sort_keys(json)
+    } else {
+        json
+    };
+    let rendered = match format.indent {
+        Some(indent) => {
+            let indent = vec![b' '; indent];
+            let mut rendered = Vec::new();
+            let formatter = serde_json::ser::PrettyFormatter::with_indent(&indent);
+            let mut serializer = serde_json::Serializer::with_formatter(&mut rendered, formatter);
+            json.serialize(&mut serializer)
+                .map_err(|e| Error::BadInput(format!("Failed to format JSON: {}", e)))?;
+            String::from_utf8_lossy(&rendered).into_owned()
+        }
+        None => json.to_string(),
+    };
+    if !label {
+        return Ok(rendered);
+    }
+    let kind = if format.sort_keys {
+        "JSON with sorted keys"
+    } else {
+        "JSON"
+    };
+    Ok(format!("Response body ({}):\n{}", kind, rendered))
+}
+
+fn sort_keys(json: serde_json::Value) -> serde_json::Value {
+    match json {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            serde_json::Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, sort_keys(value)))
+                    .collect(),
+            )
+        }
+        serde_json::Value::Array(items) => {
+            serde_json::Value::Array(items.into_iter().map(sort_keys).collect())
+        }
+        scalar => scalar,
+    }
+}
+
+/// The status line and response headers, followed by a blank line.
+pub fn format_headers(outcome: &Outcome) -> String {
+    let mut block = format!("{:?} {}\n", outcome.version, outcome.status);
+    for (name, value) in &outcome.headers {
+        block.push_str(&format!(
+            "{}: {}\n",
+            name,
+            String::from_utf8_lossy(value.as_bytes())
+        ));
+    }
+    block.push('\n');
+    block
+}
+
+/// Writes the response body to `out`. Bodies labelled as JSON that fit in
+/// `max_json_size` bytes are rendered with `format`; everything else is
+/// streamed through unchanged so binary payloads arrive intact. `label`
+/// heads the body with a "Response body" line, which only belongs next to
+/// the request summary.
+pub fn write_body<W: Write + ?Sized>(
+    outcome: &mut Outcome,
+    out: &mut W,
+    max_json_size: usize,
+    format: &JsonFormat,
+    label: bool,
+) -> Result<(), Error> {
+    let mut prefix = Vec::new();
+    if is_json(&outcome.headers) {
+        prefix = outcome.read_prefix(max_json_size.saturating_add(1))?;
+        if prefix.len() <= max_json_size {
+            if let Ok(body) = std::str::from_utf8(&prefix) {
+                if serde_json::from_str::<serde_json::Value>(body).is_ok() {
+                    return writeln!(out, "{}", format_body(body, format, label)?)
+                        .map_err(|e| Error::Write(e.to_string()));
+                }
+            }
+        }
+    }
+    if label {
+        out.write_all(b"Response body:\n")
+            .map_err(|e| Error::Write(e.to_string()))?;
+    }
+    out.write_all(&prefix)
+        .map_err(|e| Error::Write(e.to_string()))?;
+    outcome.copy_to(out)?;
+    Ok(())
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    let content_type = match headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(content_type) => content_type,
+        None => return false,
+    };
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    essence == "application/json" || essence.ends_with("+json")
+}
+
+/// The `--connect-all` report: one row per resolved address.
+pub fn format_probes(probes: &[Probe]) -> String {
+    let mut table = format!(
+        "{:<40} {:<6} {:>10}  {:<95}  {}\n",
+        "ADDRESS", "STATUS", "TIME", "CERTIFICATE SHA-256", "BODY SHA-256"
+    );
+    for probe in probes {
+        match &probe.result {
+            Ok(response) => table.push_str(&format!(
+                "{:<40} {:<6} {:>8}ms  {:<95}  {}\n",
+                probe.addr.to_string(),
+                response.status.as_u16(),
+                response.latency.as_millis(),
+                response.certificate_sha256.as_deref().unwrap_or("-"),
+                response.body_sha256
+            )),
+            Err(e) => table.push_str(&format!(
+                "{:<40} {:<6} {:>10}  Error: {}\n",
+                probe.addr.to_string(),
+                "-",
+                "-",
+                e
+            )),
+        }
+    }
+    table
+}
+
+/// Expands a `--write-out` template once the body has been read.
+/// Variables are written `%{name}`; `%{header{name}}` or `%header{name}`
+/// inserts a response header, `%{json}` every variable as one JSON
+/// object, and `%%`, `\n`, `\r` and `\t` the characters they stand for.
+/// `time_connect` is not supported, as the HTTP client does not report
+/// when its connection was established: `%{json}` leaves it out and
+/// `%{time_connect}` expands to nothing, with a warning.
+pub fn format_write_out(template: &str, outcome: &Outcome) -> String {
+    expand_write_out(template, &write_out_variables(outcome), &outcome.headers)
+}
+
+fn expand_write_out(
+    template: &str,
+    variables: &[(&'static str, WriteOutValue)],
+    headers: &HeaderMap,
+) -> String {
+    let header = |name: &str| {
+        headers
+            .get_all(name)
+            .iter()
+            .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(c) = rest.chars().next() {
+        let expansion = if let Some(after) = rest.strip_prefix("%%") {
+            Some(("%".to_string(), after))
+        } else if let Some((name, after)) = rest
+            .strip_prefix("%{header{")
+            .and_then(|after| after.split_once("}}"))
+            .or_else(|| {
+                rest.strip_prefix("%header{")
+                    .and_then(|after| after.split_once('}'))
+            })
+        {
+            Some((header(name), after))
+        } else if let Some((name, after)) = rest
+            .strip_prefix("%{")
+            .and_then(|after| after.split_once('}'))
+        {
+            let value = match name {
+                "json" => serde_json::Value::Object(
+                    variables
+                        .iter()
+                        .map(|(name, value)| (name.to_string(), value.to_json()))
+                        .collect(),
+                )
+                .to_string(),
+                name => match variables.iter().find(|(known, _)| *known == name) {
+                    Some((_, value)) => value.to_string(),
+                    None if name == "time_connect" => {
+                        eprintln!("Warning: --write-out variable 'time_connect' is not supported.");
+                        String::new()
+                    }
+                    None => {
+                        eprintln!("Warning: unknown --write-out variable: '{}'", name);
+                        String::new()
+                    }
+                },
+            };
+            Some((value, after))
+        } else if let Some(after) = rest.strip_prefix('\\') {
+            match after.chars().next() {
+                Some('n') => Some(("\n".to_string(), &after[1..])),
+                Some('r') => Some(("\r".to_string(), &after[1..])),
+                Some('t') => Some(("\t".to_string(), &after[1..])),
+                Some('\\') => Some(("\\".to_string(), &after[1..])),
+                _ => None,
+            }
+        } else {
+            None
+        };
+        match expansion {
+            Some((value, after)) => {
+                out.push_str(&value);
+                rest = after;
+            }
+            None => {
+                out.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    out
+}
+
+enum WriteOutValue {
+    Text(String),
+    Count(u64),
+    Seconds(Duration),
+}
+
+impl WriteOutValue {
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            WriteOutValue::Text(text) => serde_json::Value::from(text.as_str()),
+            WriteOutValue::Count(count) => serde_json::Value::from(*count),
+            WriteOutValue::Seconds(seconds) => {
+                serde_json::Value::from((seconds.as_secs_f64() * 1e6).round() / 1e6)
+            }
+        }
+    }
+}
+
+impl fmt::Display for WriteOutValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteOutValue::Text(text) => write!(f, "{}", text),
+            WriteOutValue::Count(count) => write!(f, "{}", count),
+            WriteOutValue::Seconds(seconds) => write!(f, "{:.6}", seconds.as_secs_f64()),
+        }
+    }
+}
+
+fn write_out_variables(outcome: &Outcome) -> Vec<(&'static str, WriteOutValue)> {
+    let content_type = outcome
+        .headers
+        .get(CONTENT_TYPE)
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+        .unwrap_or_default();
+    let status = u64::from(outcome.status.as_u16());
+    vec![
+        ("content_type", WriteOutValue::Text(content_type)),
+        ("http_code", WriteOutValue::Count(status)),
+        (
+            "num_redirects",
+            WriteOutValue::Count(u64::from(outcome.num_redirects)),
+        ),
+        (
+            "remote_ip",
+            WriteOutValue::Text(
+                outcome
+                    .remote_addr
+                    .map(|addr| addr.ip().to_string())
+                    .unwrap_or_default(),
+            ),
+        ),
+        (
+            "remote_port",
+            WriteOutValue::Count(outcome.remote_addr.map_or(0, |addr| u64::from(addr.port()))),
+        ),
+        ("response_code", WriteOutValue::Count(status)),
+        (
+            "size_download",
+            WriteOutValue::Count(outcome.size_download()),
+        ),
+        (
+            "time_starttransfer",
+            WriteOutValue::Seconds(outcome.time_starttransfer),
+        ),
+        ("time_total", WriteOutValue::Seconds(outcome.elapsed())),
+        (
+            "url_effective",
+            WriteOutValue::Text(outcome.url.to_string()),
+        ),
+    ]
+}
+
+/// Opens `path` for the response body. An existing regular file is only
+/// replaced when `clobber` is set, while devices such as `/dev/null` are
+/// always written to. Missing parent directories are only created when
+/// `create_dirs` is set.
+pub fn open_output(path: &Path, create_dirs: bool, clobber: bool) -> Result<File, Error> {
+    if create_dirs {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                Error::Write(format!("Failed to create {}: {}", parent.display(), e))
+            })?;
+        }
+    }
+    if !clobber && std::fs::metadata(path).is_ok_and(|metadata| metadata.is_file()) {
+        return Err(Error::Write(format!(
+            "{} already exists; use --clobber to overwrite it.",
+            path.display()
+        )));
+    }
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .map_err(|e| Error::Write(format!("Failed to open {}: {}", path.display(), e)))
+}
+
+/// The local file name `-O` uses for `url`: its last path segment.
+pub fn remote_name(url: &str) -> Result<PathBuf, Error> {
+    let parsed_url = Url::parse(url).map_err(Error::from_url)?;
+    match parsed_url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+    {
+        Some(name) if !name.is_empty() && name != "." && name != ".." => Ok(PathBuf::from(name)),
+        _ => Err(Error::Write(format!(
+            "Cannot derive a file name from {}; use -o instead.",
+            url
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables() -> Vec<(&'static str, WriteOutValue)> {
+        vec![
+            ("http_code", WriteOutValue::Count(200)),
+            (
+                "time_total",
+                WriteOutValue::Seconds(Duration::from_millis(1500)),
+            ),
+            (
+                "url_effective",
+                WriteOutValue::Text("http://localhost/".to_string()),
+            ),
+        ]
+    }
+
+    #[test]
+    fn sort_keys_sorts_at_every_depth() {
+        let json = serde_json::from_str(r#"{"b":[{"z":1,"a":2}],"a":{"y":1,"x":2}}"#).unwrap();
+        assert_eq!(
+            sort_keys(json).to_string(),
+            r#"{"a":{"x":2,"y":1},"b":[{"a":2,"z":1}]}"#
+        );
+    }
+
+    #[test]
+    fn format_body_keeps_order_unless_sorting() {
+        let body = r#"{"b":1,"a":2}"#;
+        let format = JsonFormat {
+            sort_keys: false,
+            indent: None,
+        };
+        assert_eq!(
+            format_body(body, &format, true).unwrap(),
+            "Response body (JSON):\n{\"b\":1,\"a\":2}"
+        );
+        assert_eq!(
+            format_body(body, &JsonFormat::default(), true).unwrap(),
+            "Response body (JSON with sorted keys):\n{\n  \"a\": 2,\n  \"b\": 1\n}"
+        );
+        assert_eq!(
+            format_body("plain\n\n", &format, true).unwrap(),
+            "Response body:\nplain"
+        );
+    }
+
+    #[test]
+    fn format_body_without_label_is_just_the_body() {
+        assert_eq!(
+            format_body(r#"{"b":1,"a":2}"#, &JsonFormat::default(), false).unwrap(),
+            "{\n  \"a\": 2,\n  \"b\": 1\n}"
+        );
+        assert_eq!(
+            format_body("plain\n", &JsonFormat::default(), false).unwrap(),
+            "plain"
+        );
+    }
+
+    #[test]
+    fn expands_variables_and_escapes() {
+        let out = expand_write_out(
+            "%{http_code} %{time_total}\\t100%% %{nope}\\n",
+            &variables(),
+            &HeaderMap::new(),
+        );
+        assert_eq!(out, "200 1.500000\t100% \n");
+    }
+
+    #[test]
+    fn expands_headers_both_ways() {
+        let mut headers = HeaderMap::new();
+        headers.append("x-a", "1".parse().unwrap());
+        headers.append("x-a", "2".parse().unwrap());
+        let out = expand_write_out("%{header{X-A}}|%header{x-b}|", &[], &headers);
+        assert_eq!(out, "1, 2||");
+    }
+
+    #[test]
+    fn json_holds_only_the_variables_there() {
+        let out = expand_write_out("%{json}", &variables(), &HeaderMap::new());
+        let json: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "http_code": 200,
+                "time_total": 1.5,
+                "url_effective": "http://localhost/",
+            })
+        );
+        assert_eq!(
+            expand_write_out("[%{time_connect}]", &variables(), &HeaderMap::new()),
+            "[]"
+        );
+    }
+}
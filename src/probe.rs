@@ -0,0 +1,76 @@
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+
+use crate::client::{prepare, transfer};
+use crate::error::Error;
+use crate::request::RequestSpec;
+use crate::resolve::Route;
+use crate::upload::spool_stdin;
+use crate::verbose::fingerprint;
+
+/// What one resolved address answered during `--connect-all`.
+#[derive(Debug)]
+pub struct Probe {
+    pub addr: SocketAddr,
+    pub result: Result<ProbeResponse, Error>,
+}
+
+#[derive(Debug)]
+pub struct ProbeResponse {
+    pub status: StatusCode,
+    /// Time from sending the request until the whole body had arrived.
+    pub latency: Duration,
+    /// SHA-256 fingerprint of the server certificate, for HTTPS.
+    pub certificate_sha256: Option<String>,
+    pub body_sha256: String,
+}
+
+/// Sends the request once to every address the host resolves to, keeping
+/// the original Host header and TLS SNI for each.
+pub fn connect_all(spec: &RequestSpec) -> Result<Vec<Probe>, Error> {
+    // Every address gets the same upload, so stdin is read only once.
+    let spooled = spool_stdin(spec)?;
+    let spec = spooled.as_ref().map_or(spec, |(_, spec)| spec);
+    let (parsed_url, route) = prepare(spec)?;
+    if spec.proxy().proxy_for(&parsed_url).is_some() {
+        return Err(Error::Unsupported(
+            "--connect-all: a proxy picks the server address itself.".to_string(),
+        ));
+    }
+    Ok(route
+        .addrs
+        .iter()
+        .map(|addr| Probe {
+            addr: *addr,
+            result: probe(
+                spec,
+                &parsed_url,
+                Route {
+                    port: route.port,
+                    addrs: vec![*addr],
+                    local_address: route.local_address,
+                },
+            ),
+        })
+        .collect())
+}
+
+fn probe(spec: &RequestSpec, parsed_url: &url::Url, route: Route) -> Result<ProbeResponse, Error> {
+    let started = Instant::now();
+    let mut outcome = transfer(spec, parsed_url, route)?;
+    let mut hasher = Sha256::new();
+    outcome.copy_to(&mut hasher)?;
+    Ok(ProbeResponse {
+        status: outcome.status,
+        latency: started.elapsed(),
+        certificate_sha256: outcome.peer_certificate.as_deref().map(fingerprint),
+        body_sha256: hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect(),
+    })
+}
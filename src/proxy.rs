@@ -0,0 +1,251 @@
+use std::net::IpAddr;
+
+use reqwest::Proxy;
+use url::Url;
+
+use crate::auth::Credentials;
+use crate::error::Error;
+
+/// Which proxy each request goes through.
+#[derive(Debug, Clone, Default)]
+pub struct ProxySettings {
+    /// Used for every scheme without a proxy of its own.
+    pub all: Option<Url>,
+    pub http: Option<Url>,
+    pub https: Option<Url>,
+    /// Overrides credentials given in the proxy URL.
+    pub credentials: Option<Credentials>,
+    pub no_proxy: NoProxy,
+}
+
+impl ProxySettings {
+    /// One proxy for every request, as given to `-x`. A missing scheme
+    /// means `http://` and a missing port means 1080.
+    pub fn with_proxy(proxy: &str) -> Result<Self, Error> {
+        Ok(ProxySettings {
+            all: Some(parse_proxy_url(proxy)?),
+            no_proxy: NoProxy::parse(&env(&["no_proxy", "NO_PROXY"]).unwrap_or_default()),
+            ..ProxySettings::default()
+        })
+    }
+
+    /// Reads `http_proxy`, `https_proxy`, `all_proxy` and `no_proxy`. Like
+    /// curl, the upper-case names are honored too, except `HTTP_PROXY`,
+    /// which a CGI script's caller can set through the `Proxy` header.
+    pub fn from_env() -> Result<Self, Error> {
+        let proxy = |names: &[&str]| env(names).as_deref().map(parse_proxy_url).transpose();
+        Ok(ProxySettings {
+            all: proxy(&["all_proxy", "ALL_PROXY"])?,
+            http: proxy(&["http_proxy"])?,
+            https: proxy(&["https_proxy", "HTTPS_PROXY"])?,
+            credentials: None,
+            no_proxy: NoProxy::parse(&env(&["no_proxy", "NO_PROXY"]).unwrap_or_default()),
+        })
+    }
+
+    /// The proxy a request to `url` goes through, if any.
+    pub(crate) fn proxy_for(&self, url: &Url) -> Option<&Url> {
+        let host = url.host_str()?;
+        if self.no_proxy.matches(host) {
+            return None;
+        }
+        match url.scheme() {
+            "https" => self.https.as_ref(),
+            _ => self.http.as_ref(),
+        }
+        .or(self.all.as_ref())
+    }
+
+    pub(crate) fn build(&self, proxy_url: &Url) -> Result<Proxy, Error> {
+        let proxy = Proxy::all(proxy_url.clone())?;
+        Ok(match &self.credentials {
+            Some(credentials) => proxy.basic_auth(credentials.user(), credentials.password()),
+            None => proxy,
+        })
+    }
+}
+
+/// Hosts that are reached directly even when a proxy is configured.
+#[derive(Debug, Clone, Default)]
+pub struct NoProxy {
+    everything: bool,
+    domains: Vec<String>,
+    networks: Vec<(IpAddr, u8)>,
+}
+
+impl NoProxy {
+    /// Parses a comma-separated list of host names, which also match their
+    /// subdomains, IP addresses and CIDR ranges; `*` alone matches every
+    /// host.
+    pub fn parse(list: &str) -> Self {
+        let mut no_proxy = NoProxy::default();
+        for entry in list
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|entry| !entry.is_empty())
+        {
+            if entry == "*" {
+                no_proxy.everything = true;
+            } else if let Some(network) = parse_network(entry) {
+                no_proxy.networks.push(network);
+            } else {
+                no_proxy
+                    .domains
+                    .push(entry.trim_start_matches('.').to_ascii_lowercase());
+            }
+        }
+        no_proxy
+    }
+
+    pub fn matches(&self, host: &str) -> bool {
+        let host = host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_ascii_lowercase();
+        if self.everything {
+            return true;
+        }
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return self
+                .networks
+                .iter()
+                .any(|(network, prefix)| in_network(ip, *network, *prefix));
+        }
+        self.domains.iter().any(|domain| {
+            host == *domain
+                || host
+                    .strip_suffix(domain.as_str())
+                    .is_some_and(|prefix| prefix.ends_with('.'))
+        })
+    }
+}
+
+fn parse_network(entry: &str) -> Option<(IpAddr, u8)> {
+    let (ip, prefix) = match entry.split_once('/') {
+        Some((ip, prefix)) => (ip, Some(prefix.parse::<u8>().ok()?)),
+        None => (entry, None),
+    };
+    let ip: IpAddr = ip
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()?;
+    let max = if ip.is_ipv4() { 32 } else { 128 };
+    let prefix = prefix.unwrap_or(max);
+    (prefix <= max).then_some((ip, prefix))
+}
+
+fn in_network(ip: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+fn parse_proxy_url(proxy: &str) -> Result<Url, Error> {
+    let with_scheme = if proxy.contains("://") {
+        proxy.to_string()
+    } else {
+        format!("http://{}", proxy)
+    };
+    let mut url = Url::parse(&with_scheme)
+        .map_err(|e| Error::BadInput(format!("Invalid proxy {}: {}", proxy, e)))?;
+    if !matches!(url.scheme(), "http" | "https" | "socks5" | "socks5h") {
+        return Err(Error::Unsupported(format!(
+            "proxy scheme {}: use http, https, socks5 or socks5h.",
+            url.scheme()
+        )));
+    }
+    if !has_port(&with_scheme) {
+        let _ = url.set_port(Some(1080));
+    }
+    Ok(url)
+}
+
+/// Whether the authority of `url` spells out a port. `Url` forgets a
+/// port that equals the scheme's default, so this reads the text.
+fn has_port(url: &str) -> bool {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = authority.rsplit('@').next().unwrap_or_default();
+    let host = host.rsplit(']').next().unwrap_or_default();
+    host.contains(':')
+}
+
+fn env(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_domains_and_subdomains() {
+        let no_proxy = NoProxy::parse("example.com, .internal.net  localhost");
+        assert!(no_proxy.matches("example.com"));
+        assert!(no_proxy.matches("API.Example.com"));
+        assert!(no_proxy.matches("db.internal.net"));
+        assert!(no_proxy.matches("localhost"));
+        assert!(!no_proxy.matches("badexample.com"));
+        assert!(!no_proxy.matches("example.org"));
+    }
+
+    #[test]
+    fn matches_cidr_ranges() {
+        let no_proxy = NoProxy::parse("10.0.0.0/8,192.168.1.7,fd00::/8,[::1]");
+        assert!(no_proxy.matches("10.255.1.2"));
+        assert!(!no_proxy.matches("11.0.0.1"));
+        assert!(no_proxy.matches("192.168.1.7"));
+        assert!(!no_proxy.matches("192.168.1.8"));
+        assert!(no_proxy.matches("[fd12:3456::1]"));
+        assert!(!no_proxy.matches("fe80::1"));
+        assert!(no_proxy.matches("::1"));
+        // A v4 range never matches a v6 address.
+        assert!(!NoProxy::parse("0.0.0.0/0").matches("::1"));
+        assert!(NoProxy::parse("0.0.0.0/0").matches("8.8.8.8"));
+    }
+
+    #[test]
+    fn ignores_invalid_prefixes_as_networks() {
+        let no_proxy = NoProxy::parse("10.0.0.0/33");
+        assert!(!no_proxy.matches("10.0.0.1"));
+    }
+
+    #[test]
+    fn star_matches_everything() {
+        assert!(NoProxy::parse("*").matches("anything.example"));
+        assert!(!NoProxy::parse("").matches("anything.example"));
+    }
+
+    #[test]
+    fn parses_proxy_urls() {
+        assert_eq!(
+            parse_proxy_url("proxy.local").unwrap().as_str(),
+            "http://proxy.local:1080/"
+        );
+        assert_eq!(
+            parse_proxy_url("http://proxy.local:80")
+                .unwrap()
+                .port_or_known_default(),
+            Some(80)
+        );
+        assert_eq!(
+            parse_proxy_url("socks5h://[::1]").unwrap().port(),
+            Some(1080)
+        );
+        assert!(matches!(
+            parse_proxy_url("ftp://proxy.local"),
+            Err(Error::Unsupported(_))
+        ));
+    }
+}
@@ -0,0 +1,309 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use reqwest::Method;
+
+use crate::auth::{Auth, AuthScheme};
+use crate::cookie::CookieJar;
+use crate::error::Error;
+use crate::form::FormPart;
+use crate::headers::HeaderArg;
+use crate::netrc::Netrc;
+use crate::proxy::ProxySettings;
+use crate::resolve::{ConnectTo, IpVersion, ResolveOverride};
+use crate::retry::RetryPolicy;
+use crate::tls::TlsOptions;
+
+/// The payload attached to a request.
+#[derive(Debug, Clone)]
+pub enum Body {
+    /// `-d`/`--data-urlencode`: already-encoded data sent as
+    /// `application/x-www-form-urlencoded`.
+    Form(String),
+    /// `--json`: a JSON document sent as `application/json`.
+    Json(String),
+    /// `-F`: fields and files sent as `multipart/form-data`.
+    Multipart(Vec<FormPart>),
+    /// `-T`: a file streamed as the body, or stdin when the path is `-`.
+    Upload(PathBuf),
+}
+
+/// Everything needed to perform one transfer.
+#[derive(Debug, Clone)]
+pub struct RequestSpec {
+    url: String,
+    method: Option<String>,
+    headers: Vec<HeaderArg>,
+    body: Option<Body>,
+    follow_redirects: bool,
+    max_redirects: Option<u32>,
+    verbose: bool,
+    resolve: Vec<ResolveOverride>,
+    connect_to: Vec<ConnectTo>,
+    ip_version: IpVersion,
+    interface: Option<String>,
+    connect_timeout: Option<Duration>,
+    max_time: Option<Duration>,
+    speed_limit: Option<(u64, Duration)>,
+    retry: RetryPolicy,
+    auth: Option<Auth>,
+    auth_scheme: AuthScheme,
+    netrc: Option<Netrc>,
+    cookies: Option<CookieJar>,
+    tls: TlsOptions,
+    proxy: ProxySettings,
+}
+
+impl RequestSpec {
+    pub fn new(url: impl Into<String>) -> Self {
+        RequestSpec {
+            url: url.into(),
+            method: None,
+            headers: Vec::new(),
+            body: None,
+            follow_redirects: false,
+            max_redirects: Some(50),
+            verbose: false,
+            resolve: Vec::new(),
+            connect_to: Vec::new(),
+            ip_version: IpVersion::Any,
+            interface: None,
+            connect_timeout: None,
+            max_time: None,
+            speed_limit: None,
+            retry: RetryPolicy::default(),
+            auth: None,
+            auth_scheme: AuthScheme::Basic,
+            netrc: None,
+            cookies: None,
+            tls: TlsOptions::default(),
+            proxy: ProxySettings::default(),
+        }
+    }
+
+    /// Sets the request method. Without one, uploads are sent as PUT, other
+    /// requests carrying a body as POST and everything else as GET.
+    pub fn with_method(mut self, method: impl Into<String>) -> Self {
+        self.method = Some(method.into());
+        self
+    }
+
+    pub fn with_header(mut self, header: HeaderArg) -> Self {
+        self.headers.push(header);
+        self
+    }
+
+    pub fn with_headers(mut self, headers: impl IntoIterator<Item = HeaderArg>) -> Self {
+        self.headers.extend(headers);
+        self
+    }
+
+    pub fn with_body(mut self, body: Body) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    /// Follows `Location` headers on 3xx responses.
+    pub fn with_follow_redirects(mut self, follow_redirects: bool) -> Self {
+        self.follow_redirects = follow_redirects;
+        self
+    }
+
+    /// Caps the number of redirects followed; `None` removes the cap.
+    pub fn with_max_redirects(mut self, max_redirects: Option<u32>) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    /// Logs the details of the transfer to stderr.
+    pub fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Uses fixed addresses for `host:port` pairs instead of DNS.
+    pub fn with_resolve(mut self, resolve: impl IntoIterator<Item = ResolveOverride>) -> Self {
+        self.resolve.extend(resolve);
+        self
+    }
+
+    /// Connects to another host and port than the URL names, while still
+    /// sending the URL's Host header and TLS SNI.
+    pub fn with_connect_to(mut self, connect_to: impl IntoIterator<Item = ConnectTo>) -> Self {
+        self.connect_to.extend(connect_to);
+        self
+    }
+
+    /// Restricts resolution and connections to one address family.
+    pub fn with_ip_version(mut self, ip_version: IpVersion) -> Self {
+        self.ip_version = ip_version;
+        self
+    }
+
+    /// Binds the source address to an IP address or a network interface.
+    pub fn with_interface(mut self, interface: impl Into<String>) -> Self {
+        self.interface = Some(interface.into());
+        self
+    }
+
+    /// Gives up when no connection is established within `timeout`.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Gives up when the whole transfer, redirects and body included, takes
+    /// longer than `max_time`.
+    pub fn with_max_time(mut self, max_time: Duration) -> Self {
+        self.max_time = Some(max_time);
+        self
+    }
+
+    /// Gives up when fewer than `bytes_per_second` arrive over `time`.
+    pub fn with_speed_limit(mut self, bytes_per_second: u64, time: Duration) -> Self {
+        self.speed_limit = Some((bytes_per_second, time));
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Authenticates to the server. Credentials are only sent to the host
+    /// of the original URL.
+    pub fn with_auth(mut self, auth: Auth) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// How credentials from `with_auth` or netrc are sent; Basic unless
+    /// set.
+    pub fn with_auth_scheme(mut self, scheme: AuthScheme) -> Self {
+        self.auth_scheme = scheme;
+        self
+    }
+
+    /// Looks up credentials for the URL's host when neither `with_auth`
+    /// nor the URL itself supplies a password.
+    pub fn with_netrc(mut self, netrc: Netrc) -> Self {
+        self.netrc = Some(netrc);
+        self
+    }
+
+    /// Sends matching cookies from `jar` and records the cookies servers
+    /// set; the updated jar is returned in [`Outcome::cookies`].
+    ///
+    /// [`Outcome::cookies`]: crate::Outcome::cookies
+    pub fn with_cookies(mut self, jar: CookieJar) -> Self {
+        self.cookies = Some(jar);
+        self
+    }
+
+    pub fn with_tls(mut self, tls: TlsOptions) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Sends requests through a proxy. Without one, environment variables
+    /// such as `http_proxy` are ignored.
+    pub fn with_proxy(mut self, proxy: ProxySettings) -> Self {
+        self.proxy = proxy;
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn method(&self) -> &str {
+        match &self.method {
+            Some(method) => method,
+            None if matches!(self.body, Some(Body::Upload(_))) => "PUT",
+            None if self.body.is_some() => "POST",
+            None => "GET",
+        }
+    }
+
+    pub fn headers(&self) -> &[HeaderArg] {
+        &self.headers
+    }
+
+    pub fn body(&self) -> Option<&Body> {
+        self.body.as_ref()
+    }
+
+    pub fn follow_redirects(&self) -> bool {
+        self.follow_redirects
+    }
+
+    pub fn max_redirects(&self) -> Option<u32> {
+        self.max_redirects
+    }
+
+    pub fn verbose(&self) -> bool {
+        self.verbose
+    }
+
+    pub fn resolve_overrides(&self) -> &[ResolveOverride] {
+        &self.resolve
+    }
+
+    pub fn connect_to(&self) -> &[ConnectTo] {
+        &self.connect_to
+    }
+
+    pub fn ip_version(&self) -> IpVersion {
+        self.ip_version
+    }
+
+    pub fn interface(&self) -> Option<&str> {
+        self.interface.as_deref()
+    }
+
+    pub fn connect_timeout(&self) -> Option<Duration> {
+        self.connect_timeout
+    }
+
+    pub fn max_time(&self) -> Option<Duration> {
+        self.max_time
+    }
+
+    pub fn speed_limit(&self) -> Option<(u64, Duration)> {
+        self.speed_limit
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
+    pub fn auth(&self) -> Option<&Auth> {
+        self.auth.as_ref()
+    }
+
+    pub fn auth_scheme(&self) -> AuthScheme {
+        self.auth_scheme
+    }
+
+    pub fn netrc(&self) -> Option<&Netrc> {
+        self.netrc.as_ref()
+    }
+
+    pub fn cookies(&self) -> Option<&CookieJar> {
+        self.cookies.as_ref()
+    }
+
+    pub fn tls(&self) -> &TlsOptions {
+        &self.tls
+    }
+
+    pub fn proxy(&self) -> &ProxySettings {
+        &self.proxy
+    }
+
+    pub(crate) fn request_method(&self) -> Result<Method, Error> {
+        let method = self.method();
+        Method::from_bytes(method.as_bytes())
+            .map_err(|_| Error::BadInput(format!("Invalid request method: {}.", method)))
+    }
+}
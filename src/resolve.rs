@@ -0,0 +1,287 @@
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+
+use url::Url;
+
+use crate::auth::redact_url;
+use crate::error::Error;
+use crate::request::RequestSpec;
+use crate::verbose;
+
+/// A `--resolve host:port:addr[,addr]` entry.
+#[derive(Debug, Clone)]
+pub struct ResolveOverride {
+    host: String,
+    port: u16,
+    addrs: Vec<IpAddr>,
+}
+
+impl ResolveOverride {
+    pub fn parse(arg: &str) -> Result<Self, Error> {
+        let invalid = || Error::BadInput(format!("Invalid --resolve entry: {}", arg));
+        let mut fields = arg.splitn(3, ':');
+        let host = fields
+            .next()
+            .filter(|host| !host.is_empty())
+            .ok_or_else(invalid)?;
+        let port = fields
+            .next()
+            .and_then(|port| port.parse().ok())
+            .ok_or_else(invalid)?;
+        let addrs = fields
+            .next()
+            .ok_or_else(invalid)?
+            .split(',')
+            .map(|addr| parse_ip(addr).ok_or_else(invalid))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ResolveOverride {
+            host: host.to_ascii_lowercase(),
+            port,
+            addrs,
+        })
+    }
+
+    fn matches(&self, host: &str, port: u16) -> bool {
+        self.host.eq_ignore_ascii_case(host) && self.port == port
+    }
+}
+
+/// A `--connect-to host1:port1:host2:port2` entry. Empty fields match any
+/// host or port on the left and keep the original one on the right.
+#[derive(Debug, Clone)]
+pub struct ConnectTo {
+    host: Option<String>,
+    port: Option<u16>,
+    target_host: Option<String>,
+    target_port: Option<u16>,
+}
+
+impl ConnectTo {
+    pub fn parse(arg: &str) -> Result<Self, Error> {
+        let invalid = || Error::BadInput(format!("Invalid --connect-to entry: {}", arg));
+        let (host, rest) = split_host(arg).ok_or_else(invalid)?;
+        let (port, rest) = rest.split_once(':').ok_or_else(invalid)?;
+        let (target_host, target_port) = split_host(rest).ok_or_else(invalid)?;
+        let parse_port = |port: &str| -> Result<Option<u16>, Error> {
+            if port.is_empty() {
+                Ok(None)
+            } else {
+                port.parse().map(Some).map_err(|_| invalid())
+            }
+        };
+        let non_empty = |host: &str| Some(host.to_string()).filter(|host| !host.is_empty());
+        Ok(ConnectTo {
+            host: non_empty(host),
+            port: parse_port(port)?,
+            target_host: non_empty(target_host),
+            target_port: parse_port(target_port)?,
+        })
+    }
+
+    fn matches(&self, host: &str, port: u16) -> bool {
+        self.host
+            .as_deref()
+            .is_none_or(|own| own.eq_ignore_ascii_case(host))
+            && self.port.is_none_or(|own| own == port)
+    }
+}
+
+/// Which address family `-4`/`-6` restrict connections to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IpVersion {
+    #[default]
+    Any,
+    V4,
+    V6,
+}
+
+impl IpVersion {
+    fn allows(self, ip: &IpAddr) -> bool {
+        match self {
+            IpVersion::Any => true,
+            IpVersion::V4 => ip.is_ipv4(),
+            IpVersion::V6 => ip.is_ipv6(),
+        }
+    }
+}
+
+/// Where one hop of a transfer actually connects to.
+#[derive(Debug, Clone)]
+pub(crate) struct Route {
+    pub port: u16,
+    pub addrs: Vec<SocketAddr>,
+    /// The source address to bind, from `--interface`.
+    pub local_address: Option<IpAddr>,
+}
+
+/// Resolves the host of `url`, applying `--connect-to` and `--resolve`.
+/// Behind a proxy nothing is resolved and the route has no addresses.
+pub(crate) fn route(spec: &RequestSpec, url: &Url) -> Result<Route, Error> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| Error::MalformedUrl("The URL has no host.".to_string()))?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    if let Some(proxy) = spec.proxy().proxy_for(url) {
+        if spec.verbose() {
+            eprintln!("* Using proxy {}", redact_url(proxy.as_str()));
+        }
+        return Ok(Route {
+            port,
+            addrs: Vec::new(),
+            local_address: local_address(spec)?,
+        });
+    }
+
+    let (target_host, target_port) = match spec.connect_to().iter().find(|c| c.matches(host, port))
+    {
+        Some(connect_to) => (
+            connect_to.target_host.as_deref().unwrap_or(host),
+            connect_to.target_port.unwrap_or(port),
+        ),
+        None => (host, port),
+    };
+
+    let addrs: Vec<SocketAddr> = match spec
+        .resolve_overrides()
+        .iter()
+        .find(|o| o.matches(target_host, target_port))
+    {
+        Some(entry) => entry
+            .addrs
+            .iter()
+            .map(|ip| SocketAddr::new(*ip, target_port))
+            .collect(),
+        None => match (target_host, target_port).to_socket_addrs() {
+            Ok(addrs) => addrs.collect(),
+            Err(_) => return Err(Error::CouldNotResolveHost(target_host.to_string())),
+        },
+    };
+    // A bound source address only reaches peers of its own family.
+    let local_address = local_address(spec)?;
+    let family = match local_address {
+        Some(IpAddr::V4(_)) => IpVersion::V4,
+        Some(IpAddr::V6(_)) => IpVersion::V6,
+        None => spec.ip_version(),
+    };
+    let addrs: Vec<SocketAddr> = addrs
+        .into_iter()
+        .filter(|addr| family.allows(&addr.ip()))
+        .collect();
+    if addrs.is_empty() {
+        return Err(Error::CouldNotResolveHost(target_host.to_string()));
+    }
+    if spec.verbose() {
+        verbose::resolved(target_host, &addrs);
+        if let Some(ip) = local_address {
+            eprintln!("* Binding to local address {}", ip);
+        }
+    }
+    Ok(Route {
+        port: target_port,
+        addrs,
+        local_address,
+    })
+}
+
+/// The source address for `--interface`, which names either an address or
+/// a network interface whose first address of an allowed family is used.
+fn local_address(spec: &RequestSpec) -> Result<Option<IpAddr>, Error> {
+    let interface = match spec.interface() {
+        Some(interface) => interface,
+        None => return Ok(None),
+    };
+    let unusable = || Error::Interface(interface.to_string());
+    if let Some(ip) = parse_ip(interface) {
+        return match spec.ip_version().allows(&ip) {
+            true => Ok(Some(ip)),
+            false => Err(unusable()),
+        };
+    }
+    let interfaces = if_addrs::get_if_addrs().map_err(|_| unusable())?;
+    interfaces
+        .iter()
+        .filter(|candidate| candidate.name == interface)
+        .map(|candidate| candidate.ip())
+        .find(|ip| spec.ip_version().allows(ip))
+        .map(Some)
+        .ok_or_else(unusable)
+}
+
+/// Splits a leading host, which may be a bracketed IPv6 address, from the
+/// `:`-separated rest.
+fn split_host(input: &str) -> Option<(&str, &str)> {
+    if input.starts_with('[') {
+        let end = input.find(']')?;
+        let rest = input[end + 1..].strip_prefix(':')?;
+        Some((&input[1..end], rest))
+    } else {
+        input.split_once(':')
+    }
+}
+
+fn parse_ip(input: &str) -> Option<IpAddr> {
+    input
+        .trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_resolve_entries() {
+        let entry = ResolveOverride::parse("Example.com:443:127.0.0.1,[::1]").unwrap();
+        assert_eq!(entry.host, "example.com");
+        assert_eq!(entry.port, 443);
+        assert_eq!(
+            entry.addrs,
+            vec![
+                "127.0.0.1".parse::<IpAddr>().unwrap(),
+                "::1".parse().unwrap()
+            ]
+        );
+        assert!(entry.matches("EXAMPLE.com", 443));
+        assert!(!entry.matches("example.com", 80));
+    }
+
+    #[test]
+    fn rejects_bad_resolve_entries() {
+        assert!(ResolveOverride::parse("example.com:443").is_err());
+        assert!(ResolveOverride::parse(":443:127.0.0.1").is_err());
+        assert!(ResolveOverride::parse("example.com:http:127.0.0.1").is_err());
+        assert!(ResolveOverride::parse("example.com:443:not-an-ip").is_err());
+    }
+
+    #[test]
+    fn parses_connect_to_entries() {
+        let entry = ConnectTo::parse("example.com:443:[::1]:8443").unwrap();
+        assert_eq!(entry.host.as_deref(), Some("example.com"));
+        assert_eq!(entry.port, Some(443));
+        assert_eq!(entry.target_host.as_deref(), Some("::1"));
+        assert_eq!(entry.target_port, Some(8443));
+        assert!(entry.matches("Example.COM", 443));
+        assert!(!entry.matches("example.com", 80));
+    }
+
+    #[test]
+    fn empty_connect_to_fields_match_anything() {
+        let entry = ConnectTo::parse("::backend:").unwrap();
+        assert_eq!(entry.host, None);
+        assert_eq!(entry.port, None);
+        assert_eq!(entry.target_host.as_deref(), Some("backend"));
+        assert_eq!(entry.target_port, None);
+        assert!(entry.matches("anything", 1234));
+    }
+
+    #[test]
+    fn rejects_bad_connect_to_entries() {
+        assert!(ConnectTo::parse("example.com:443:backend").is_err());
+        assert!(ConnectTo::parse("example.com:x:backend:80").is_err());
+        assert!(ConnectTo::parse("[::1:443:backend:80").is_err());
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant, SystemTime};
+
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
+
+use crate::client::Outcome;
+use crate::error::Error;
+
+/// How `--retry` and its companion options retry a failed transfer.
+#[derive(Debug, Clone, Default)]
+pub struct RetryPolicy {
+    /// How many times to retry after the first attempt.
+    pub retries: u32,
+    /// A fixed wait between attempts instead of exponential backoff.
+    pub delay: Option<Duration>,
+    /// No retry is started once this much time has passed since the first
+    /// attempt.
+    pub max_time: Option<Duration>,
+    /// Retry every failure, not only timeouts, broken connections and
+    /// 408/429/500/502/503/504 responses.
+    pub all_errors: bool,
+}
+
+const MAX_BACKOFF: Duration = Duration::from_secs(600);
+
+/// Runs `attempt` until it succeeds, fails permanently or the policy is
+/// exhausted. Only failures up to the response head are retried; once an
+/// outcome is returned its body belongs to the caller.
+pub(crate) fn retry<F>(policy: &RetryPolicy, mut attempt: F) -> Result<Outcome, Error>
+where
+    F: FnMut() -> Result<Outcome, Error>,
+{
+    let started = Instant::now();
+    let mut retries_left = policy.retries;
+    let mut backoff = Duration::from_secs(1);
+    loop {
+        let result = attempt();
+        let (reason, retry_after) = match &result {
+            Ok(outcome) if should_retry_status(policy, outcome.status) => (
+                format!("HTTP error {}", outcome.status.as_u16()),
+                retry_after(outcome),
+            ),
+            Err(e) if should_retry_error(policy, e) => (e.to_string(), None),
+            _ => return result,
+        };
+        if retries_left == 0 {
+            return result;
+        }
+
+        let wait = match (retry_after, policy.delay) {
+            (Some(wait), _) => wait,
+            (None, Some(delay)) => delay,
+            (None, None) => jitter(backoff),
+        };
+        if policy
+            .max_time
+            .is_some_and(|max_time| started.elapsed() + wait > max_time)
+        {
+            return result;
+        }
+
+        eprintln!(
+            "Warning: Problem: {}. Will retry in {:.1} seconds. {} {} left.",
+            reason.trim_end_matches('.'),
+            wait.as_secs_f64(),
+            retries_left,
+            if retries_left == 1 {
+                "retry"
+            } else {
+                "retries"
+            }
+        );
+        drop(result);
+        std::thread::sleep(wait);
+        retries_left -= 1;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+fn should_retry_status(policy: &RetryPolicy, status: StatusCode) -> bool {
+    matches!(status.as_u16(), 408 | 429 | 500 | 502 | 503 | 504)
+        || (policy.all_errors && (status.is_client_error() || status.is_server_error()))
+}
+
+fn should_retry_error(policy: &RetryPolicy, e: &Error) -> bool {
+    policy.all_errors
+        || matches!(
+            e,
+            Error::CouldNotConnect(_)
+                | Error::ConnectTimeout(_)
+                | Error::OperationTimeout(_)
+                | Error::SpeedTooLow(_, _)
+                | Error::Timeout(_)
+                | Error::Transfer(_)
+        )
+}
+
+/// The wait a 429 or 503 response asks for, in seconds or as a date.
+fn retry_after(outcome: &Outcome) -> Option<Duration> {
+    if !matches!(outcome.status.as_u16(), 429 | 503) {
+        return None;
+    }
+    let value = outcome.headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// Picks a wait between half and all of `backoff` so that clients failing
+/// together do not retry together.
+fn jitter(backoff: Duration) -> Duration {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(Instant::now().elapsed().as_nanos());
+    let fraction = (hasher.finish() % 1000) as f64 / 1000.0;
+    backoff / 2 + backoff.mul_f64(fraction / 2.0)
+}
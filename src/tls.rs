@@ -0,0 +1,872 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use openssl::asn1::Asn1Time;
+use openssl::pkcs12::Pkcs12;
+use openssl::pkey::PKey;
+use openssl::x509::X509;
+use reqwest::blocking::ClientBuilder;
+use reqwest::tls::{Certificate, Identity, Version};
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{RootCertStore, ServerName};
+use sha2::{Digest, Sha256};
+
+use crate::error::Error;
+
+/// Certificate verification and client certificate settings for HTTPS.
+#[derive(Debug, Clone, Default)]
+pub struct TlsOptions {
+    /// Skip verifying the server certificate and host name.
+    pub insecure: bool,
+    /// A PEM bundle that replaces the built-in trusted roots.
+    pub ca_cert: Option<PathBuf>,
+    /// A directory of PEM certificates that replaces the built-in roots.
+    pub ca_path: Option<PathBuf>,
+    pub client_cert: Option<ClientCert>,
+    pub min_version: Option<TlsVersion>,
+    pub max_version: Option<TlsVersion>,
+    /// Checked during the handshake of every connection, before anything
+    /// is sent on it. Pinning moves the connection to rustls, as the
+    /// native backend offers no hook into its certificate checks.
+    pub pinned_public_key: Option<PublicKeyPin>,
+}
+
+/// A client certificate for mutual TLS.
+#[derive(Debug, Clone)]
+pub struct ClientCert {
+    pub path: PathBuf,
+    /// The PKCS#8 private key for a PEM certificate, if it is not in `path`.
+    pub key: Option<PathBuf>,
+    /// The password of a PKCS#12 archive.
+    pub password: Option<String>,
+    pub kind: CertKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CertKind {
+    Pem,
+    Pkcs12,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TlsVersion {
+    Tls1_0,
+    Tls1_1,
+    Tls1_2,
+    Tls1_3,
+}
+
+impl TlsVersion {
+    /// Parses a `--tls-max` value such as `1.2`.
+    pub fn parse(arg: &str) -> Result<Self, Error> {
+        match arg {
+            "1.0" => Ok(TlsVersion::Tls1_0),
+            "1.1" => Ok(TlsVersion::Tls1_1),
+            "1.2" => Ok(TlsVersion::Tls1_2),
+            "1.3" => Ok(TlsVersion::Tls1_3),
+            _ => Err(Error::BadInput(format!("Unknown TLS version: {}", arg))),
+        }
+    }
+
+    fn version(self) -> Version {
+        match self {
+            TlsVersion::Tls1_0 => Version::TLS_1_0,
+            TlsVersion::Tls1_1 => Version::TLS_1_1,
+            TlsVersion::Tls1_2 => Version::TLS_1_2,
+            TlsVersion::Tls1_3 => Version::TLS_1_3,
+        }
+    }
+}
+
+/// SHA-256 hashes of the public keys the server may present.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicKeyPin {
+    hashes: Vec<[u8; 32]>,
+}
+
+impl PublicKeyPin {
+    /// Parses `sha256//<base64>[;sha256//<base64>]...`, or reads a PEM or
+    /// DER public key from a file.
+    pub fn parse(arg: &str) -> Result<Self, Error> {
+        if arg.starts_with("sha256//") {
+            let hashes = arg
+                .split(';')
+                .map(|pin| {
+                    pin.strip_prefix("sha256//")
+                        .and_then(|hash| STANDARD.decode(hash.trim()).ok())
+                        .and_then(|hash| <[u8; 32]>::try_from(hash).ok())
+                        .ok_or_else(|| Error::BadInput(format!("Invalid public key pin: {}", pin)))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            return Ok(PublicKeyPin { hashes });
+        }
+        let contents = std::fs::read(arg).map_err(|e| {
+            Error::BadInput(format!("Failed to read pinned public key {}: {}", arg, e))
+        })?;
+        let der = match pem_block(&contents, "PUBLIC KEY") {
+            Some(der) => der,
+            None => contents,
+        };
+        Ok(PublicKeyPin {
+            hashes: vec![Sha256::digest(&der).into()],
+        })
+    }
+
+    /// Checks the public key of the server's DER-encoded certificate.
+    pub(crate) fn verify(&self, certificate: Option<&[u8]>) -> Result<(), Error> {
+        let key = certificate
+            .and_then(subject_public_key_info)
+            .ok_or_else(|| {
+                Error::PinnedPublicKey("the server presented no certificate".to_string())
+            })?;
+        let hash: [u8; 32] = Sha256::digest(key).into();
+        if self.hashes.contains(&hash) {
+            Ok(())
+        } else {
+            Err(Error::PinnedPublicKey(format!(
+                "the server key is sha256//{}",
+                STANDARD.encode(hash)
+            )))
+        }
+    }
+}
+
+/// Applies `options` to a client that is about to be built.
+pub(crate) fn configure(
+    mut builder: ClientBuilder,
+    options: &TlsOptions,
+) -> Result<ClientBuilder, Error> {
+    if let Some(pin) = &options.pinned_public_key {
+        return Ok(builder.use_preconfigured_tls(pinned_config(options, pin)?));
+    }
+    if options.insecure {
+        builder = builder
+            .danger_accept_invalid_certs(true)
+            .danger_accept_invalid_hostnames(true);
+    }
+    if options.ca_cert.is_some() || options.ca_path.is_some() {
+        builder = builder.tls_built_in_root_certs(false);
+    }
+    if let Some(path) = &options.ca_cert {
+        for certificate in read_bundle(path)? {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    if let Some(dir) = &options.ca_path {
+        for path in ca_path_files(dir)? {
+            // Hash links and unrelated files that hold no PEM certificate
+            // are skipped, as OpenSSL does.
+            for certificate in read_bundle(&path).unwrap_or_default() {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+    }
+    if let Some(client_cert) = &options.client_cert {
+        builder = builder.identity(identity(client_cert)?);
+    }
+    if let Some(version) = options.min_version {
+        if version == TlsVersion::Tls1_3 {
+            return Err(Error::Unsupported(
+                "--tlsv1.3: the TLS backend cannot require TLS 1.3.".to_string(),
+            ));
+        }
+        builder = builder.min_tls_version(version.version());
+    }
+    // TLS 1.3 is the highest version the backend knows, so it needs no cap.
+    if let Some(version) = options.max_version.filter(|v| *v < TlsVersion::Tls1_3) {
+        builder = builder.max_tls_version(version.version());
+    }
+    Ok(builder)
+}
+
+fn read_bundle(path: &Path) -> Result<Vec<Certificate>, Error> {
+    let contents = std::fs::read(path).map_err(|e| ca_error(path, e))?;
+    let certificates = Certificate::from_pem_bundle(&contents).map_err(|e| ca_error(path, e))?;
+    if certificates.is_empty() {
+        return Err(ca_error(path, "no PEM certificates found"));
+    }
+    Ok(certificates)
+}
+
+/// The regular files in a `--capath` directory.
+fn ca_path_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir).map_err(|e| ca_error(dir, e))? {
+        let path = entry.map_err(|e| ca_error(dir, e))?.path();
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+fn ca_error(path: &Path, e: impl std::fmt::Display) -> Error {
+    Error::CaCertificate(format!("{}: {}", path.display(), e))
+}
+
+/// A rustls configuration with the settings `configure` gives the native
+/// backend, whose verifier also checks the server key against `pin`.
+fn pinned_config(options: &TlsOptions, pin: &PublicKeyPin) -> Result<rustls::ClientConfig, Error> {
+    let versions: Vec<_> = [
+        (TlsVersion::Tls1_2, &rustls::version::TLS12),
+        (TlsVersion::Tls1_3, &rustls::version::TLS13),
+    ]
+    .into_iter()
+    .filter(|(version, _)| {
+        options.min_version.is_none_or(|min| *version >= min)
+            && options.max_version.is_none_or(|max| *version <= max)
+    })
+    .map(|(_, version)| version)
+    .collect();
+    if versions.is_empty() {
+        return Err(Error::Unsupported(
+            "--pinnedpubkey: keys can only be pinned over TLS 1.2 and 1.3.".to_string(),
+        ));
+    }
+    let (webpki, trusted) = match options.insecure {
+        true => (None, Vec::new()),
+        false => {
+            let (roots, trusted) = pinned_roots(options)?;
+            (Some(WebPkiVerifier::new(roots, None)), trusted)
+        }
+    };
+    let verifier = PinnedVerifier {
+        webpki,
+        trusted,
+        pin: pin.clone(),
+    };
+    let builder = rustls::ClientConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(&versions)
+        .map_err(|e| Error::TlsHandshake(e.to_string()))?
+        .with_custom_certificate_verifier(Arc::new(verifier));
+    match &options.client_cert {
+        Some(client_cert) => {
+            let (path, chain, key) = client_auth(client_cert)?;
+            builder
+                .with_client_auth_cert(chain, key)
+                .map_err(|e| cert_error(path, &e))
+        }
+        None => Ok(builder.with_no_client_auth()),
+    }
+}
+
+/// The roots `--cacert` and `--capath` name, or the system's when neither
+/// is set, along with the certificates the options named.
+fn pinned_roots(options: &TlsOptions) -> Result<(RootCertStore, Vec<Vec<u8>>), Error> {
+    let mut roots = RootCertStore::empty();
+    if options.ca_cert.is_none() && options.ca_path.is_none() {
+        let native = rustls_native_certs::load_native_certs()
+            .map_err(|e| Error::CaCertificate(format!("system roots: {}", e)))?;
+        let native: Vec<_> = native
+            .into_iter()
+            .map(|certificate| certificate.0)
+            .collect();
+        roots.add_parsable_certificates(&native);
+        return Ok((roots, Vec::new()));
+    }
+    let mut named = Vec::new();
+    if let Some(path) = &options.ca_cert {
+        for der in read_pem_certificates(path)? {
+            roots
+                .add(&rustls::Certificate(der.clone()))
+                .map_err(|e| ca_error(path, e))?;
+            named.push(der);
+        }
+    }
+    if let Some(dir) = &options.ca_path {
+        for path in ca_path_files(dir)? {
+            let certificates = read_pem_certificates(&path).unwrap_or_default();
+            roots.add_parsable_certificates(&certificates);
+            named.extend(certificates);
+        }
+    }
+    Ok((roots, named))
+}
+
+/// Checks the dates and names of a server certificate that is trusted as
+/// it is, for lack of a chain webpki accepts.
+fn verify_trusted(certificate: &[u8], server_name: &ServerName) -> Result<(), rustls::Error> {
+    let invalid = |e: rustls::CertificateError| rustls::Error::InvalidCertificate(e);
+    let x509 =
+        X509::from_der(certificate).map_err(|_| invalid(rustls::CertificateError::BadEncoding))?;
+    let now = Asn1Time::days_from_now(0).map_err(|e| rustls::Error::General(e.to_string()))?;
+    if x509.not_before() > now {
+        return Err(invalid(rustls::CertificateError::NotValidYet));
+    }
+    if x509.not_after() < now {
+        return Err(invalid(rustls::CertificateError::Expired));
+    }
+    let ip;
+    let name = match server_name {
+        ServerName::DnsName(name) => webpki::DnsNameRef::try_from_ascii_str(name.as_ref())
+            .map(webpki::SubjectNameRef::from)
+            .map_err(|_| invalid(rustls::CertificateError::NotValidForName))?,
+        ServerName::IpAddress(addr) => {
+            ip = webpki::IpAddr::from(*addr);
+            webpki::SubjectNameRef::from(webpki::IpAddrRef::from(&ip))
+        }
+        _ => return Err(invalid(rustls::CertificateError::NotValidForName)),
+    };
+    webpki::EndEntityCert::try_from(certificate)
+        .and_then(|certificate| certificate.verify_is_valid_for_subject_name(name))
+        .map_err(|_| invalid(rustls::CertificateError::NotValidForName))
+}
+
+/// The DER encodings of the PEM certificates in `path`.
+fn read_pem_certificates(path: &Path) -> Result<Vec<Vec<u8>>, Error> {
+    let contents = std::fs::read(path).map_err(|e| ca_error(path, e))?;
+    let certificates: Vec<Vec<u8>> = X509::stack_from_pem(&contents)
+        .and_then(|stack| {
+            stack
+                .iter()
+                .map(|certificate| certificate.to_der())
+                .collect()
+        })
+        .map_err(|e| ca_error(path, e))?;
+    if certificates.is_empty() {
+        return Err(ca_error(path, "no PEM certificates found"));
+    }
+    Ok(certificates)
+}
+
+/// Verifies the server as usual, unless `-k` is set, and then checks its
+/// key against the pin, all within the handshake.
+struct PinnedVerifier {
+    webpki: Option<WebPkiVerifier>,
+    /// Certificates `--cacert` or `--capath` named. The server may present
+    /// one of them itself, as OpenSSL allows, even when webpki refuses it
+    /// as a leaf, such as a self-signed certificate marked as a CA.
+    trusted: Vec<Vec<u8>>,
+    pin: PublicKeyPin,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &rustls::Certificate,
+        intermediates: &[rustls::Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if let Some(webpki) = &self.webpki {
+            let verified = webpki.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                scts,
+                ocsp_response,
+                now,
+            );
+            match verified {
+                Err(_) if self.trusted.contains(&end_entity.0) => {
+                    verify_trusted(&end_entity.0, server_name)?
+                }
+                verified => {
+                    verified?;
+                }
+            }
+        }
+        self.pin
+            .verify(Some(&end_entity.0))
+            .map_err(|e| rustls::Error::General(e.to_string()))?;
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// The certificate chain and PKCS#8 key of `client_cert` for rustls,
+/// along with the path that errors about them should name.
+fn client_auth(
+    client_cert: &ClientCert,
+) -> Result<(&Path, Vec<rustls::Certificate>, rustls::PrivateKey), Error> {
+    let (path, files) = read_identity(client_cert)?;
+    let stack_error = |e: openssl::error::ErrorStack| cert_error(path, &e);
+    let (chain, key) = match files {
+        IdentityFiles::Pkcs12(der) => {
+            let parsed = Pkcs12::from_der(&der)
+                .and_then(|archive| {
+                    archive.parse2(client_cert.password.as_deref().unwrap_or_default())
+                })
+                .map_err(stack_error)?;
+            let key = parsed
+                .pkey
+                .ok_or_else(|| cert_error(path, &"no private key found"))?;
+            let chain: Vec<_> = parsed
+                .cert
+                .into_iter()
+                .chain(parsed.ca.into_iter().flatten())
+                .collect();
+            (chain, key)
+        }
+        IdentityFiles::Pem { certificates, key } => (
+            X509::stack_from_pem(certificates.as_bytes()).map_err(stack_error)?,
+            PKey::private_key_from_pem(key.as_bytes()).map_err(stack_error)?,
+        ),
+    };
+    let chain = chain
+        .iter()
+        .map(|certificate| certificate.to_der().map(rustls::Certificate))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(stack_error)?;
+    let key = key.private_key_to_pkcs8().map_err(stack_error)?;
+    Ok((path, chain, rustls::PrivateKey(key)))
+}
+
+fn identity(client_cert: &ClientCert) -> Result<Identity, Error> {
+    let (path, files) = read_identity(client_cert)?;
+    match files {
+        IdentityFiles::Pkcs12(der) => {
+            Identity::from_pkcs12_der(&der, client_cert.password.as_deref().unwrap_or_default())
+        }
+        IdentityFiles::Pem { certificates, key } => {
+            Identity::from_pkcs8_pem(certificates.as_bytes(), key.as_bytes())
+        }
+    }
+    .map_err(|e| cert_error(path, &e))
+}
+
+/// A client certificate as read from disk.
+enum IdentityFiles {
+    Pkcs12(Vec<u8>),
+    /// The certificates and the PKCS#8 key as separate PEM blocks, the
+    /// way the TLS backends want them.
+    Pem {
+        certificates: String,
+        key: String,
+    },
+}
+
+/// Reads the files of `client_cert`, along with the path that errors
+/// about the resulting identity should name.
+fn read_identity(client_cert: &ClientCert) -> Result<(&Path, IdentityFiles), Error> {
+    let contents =
+        std::fs::read(&client_cert.path).map_err(|e| cert_error(&client_cert.path, &e))?;
+    match client_cert.kind {
+        CertKind::Pkcs12 => Ok((&client_cert.path, IdentityFiles::Pkcs12(contents))),
+        CertKind::Pem => {
+            let key_path = client_cert.key.as_deref().unwrap_or(&client_cert.path);
+            let key = match &client_cert.key {
+                Some(path) => std::fs::read(path).map_err(|e| cert_error(path, &e))?,
+                None => contents.clone(),
+            };
+            let certificates = pem_blocks(&contents, "CERTIFICATE");
+            let key = pem_blocks(&key, "PRIVATE KEY");
+            if key.is_empty() {
+                return Err(cert_error(key_path, &"no PKCS#8 private key found"));
+            }
+            Ok((key_path, IdentityFiles::Pem { certificates, key }))
+        }
+    }
+}
+
+fn cert_error(path: &Path, e: &dyn std::fmt::Display) -> Error {
+    Error::Certificate(format!("{}: {}", path.display(), e))
+}
+
+/// The `-----BEGIN <label>-----` blocks of a PEM file, verbatim.
+fn pem_blocks(contents: &[u8], label: &str) -> String {
+    let text = String::from_utf8_lossy(contents);
+    let begin = format!("-----BEGIN {}-----", label);
+    let end = format!("-----END {}-----", label);
+    let mut blocks = String::new();
+    let mut rest = text.as_ref();
+    while let Some(start) = rest.find(&begin) {
+        let Some(stop) = rest[start..].find(&end) else {
+            break;
+        };
+        blocks.push_str(&rest[start..start + stop + end.len()]);
+        blocks.push('\n');
+        rest = &rest[start + stop + end.len()..];
+    }
+    blocks
+}
+
+/// Decodes the first `-----BEGIN <label>-----` block of a PEM file.
+fn pem_block(contents: &[u8], label: &str) -> Option<Vec<u8>> {
+    let text = std::str::from_utf8(contents).ok()?;
+    let begin = format!("-----BEGIN {}-----", label);
+    let end = format!("-----END {}-----", label);
+    let body = text.split_once(&begin)?.1.split_once(&end)?.0;
+    let body: String = body.split_whitespace().collect();
+    STANDARD.decode(body).ok()
+}
+
+/// Extracts the DER-encoded SubjectPublicKeyInfo from an X.509
+/// certificate: the seventh field of `tbsCertificate`, counting the
+/// optional `[0]` version.
+fn subject_public_key_info(certificate: &[u8]) -> Option<&[u8]> {
+    let certificate = der_element(certificate)?;
+    let tbs = der_element(certificate.content)?;
+    let mut rest = tbs.content;
+    if der_element(rest)?.tag == 0xa0 {
+        rest = &rest[der_element(rest)?.len..];
+    }
+    // serialNumber, signature, issuer, validity, subject
+    for _ in 0..5 {
+        rest = &rest[der_element(rest)?.len..];
+    }
+    let key = der_element(rest)?;
+    (key.tag == 0x30).then(|| &rest[..key.len])
+}
+
+/// The subject and issuer of an X.509 certificate, written as
+/// `CN=..., O=...` with the attributes that have a short name.
+pub(crate) fn certificate_names(certificate: &[u8]) -> Option<(String, String)> {
+    let certificate = der_element(certificate)?;
+    let tbs = der_element(certificate.content)?;
+    let mut rest = tbs.content;
+    if der_element(rest)?.tag == 0xa0 {
+        rest = &rest[der_element(rest)?.len..];
+    }
+    // serialNumber, signature
+    for _ in 0..2 {
+        rest = &rest[der_element(rest)?.len..];
+    }
+    let issuer = der_element(rest)?;
+    rest = &rest[issuer.len..];
+    rest = &rest[der_element(rest)?.len..];
+    let subject = der_element(rest)?;
+    Some((
+        distinguished_name(subject.content)?,
+        distinguished_name(issuer.content)?,
+    ))
+}
+
+/// Renders the RDNs of a `Name`, each a SET of `{ type, value }` pairs.
+fn distinguished_name(mut rdns: &[u8]) -> Option<String> {
+    let mut parts = Vec::new();
+    while !rdns.is_empty() {
+        let set = der_element(rdns)?;
+        rdns = &rdns[set.len..];
+        let mut attributes = set.content;
+        while !attributes.is_empty() {
+            let attribute = der_element(attributes)?;
+            attributes = &attributes[attribute.len..];
+            let oid = der_element(attribute.content)?;
+            let value = der_element(&attribute.content[oid.len..])?;
+            let name = match oid.content {
+                [0x55, 0x04, 0x03] => "CN",
+                [0x55, 0x04, 0x06] => "C",
+                [0x55, 0x04, 0x07] => "L",
+                [0x55, 0x04, 0x08] => "ST",
+                [0x55, 0x04, 0x0a] => "O",
+                [0x55, 0x04, 0x0b] => "OU",
+                _ => continue,
+            };
+            parts.push(format!(
+                "{}={}",
+                name,
+                String::from_utf8_lossy(value.content)
+            ));
+        }
+    }
+    Some(parts.join(", "))
+}
+
+struct DerElement<'a> {
+    tag: u8,
+    /// Length of the whole element, header included.
+    len: usize,
+    content: &'a [u8],
+}
+
+/// Reads the DER element at the start of `input`.
+fn der_element(input: &[u8]) -> Option<DerElement<'_>> {
+    let tag = *input.first()?;
+    let first = *input.get(1)?;
+    let (header, content_len) = if first < 0x80 {
+        (2, usize::from(first))
+    } else {
+        let count = usize::from(first & 0x7f);
+        if count == 0 || count > std::mem::size_of::<usize>() {
+            return None;
+        }
+        let bytes = input.get(2..2 + count)?;
+        let len = bytes
+            .iter()
+            .fold(0usize, |len, byte| (len << 8) | usize::from(*byte));
+        (2 + count, len)
+    };
+    let len = header.checked_add(content_len)?;
+    Some(DerElement {
+        tag,
+        len,
+        content: input.get(header..len)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use openssl::asn1::Asn1Time;
+    use openssl::bn::BigNum;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::pkey::Private;
+    use openssl::ssl::{SslAcceptor, SslMethod};
+    use openssl::x509::extension::{BasicConstraints, SubjectAlternativeName};
+    use openssl::x509::X509NameBuilder;
+
+    use super::*;
+    use crate::{execute, RequestSpec, ResolveOverride};
+
+    fn self_signed() -> (PKey<Private>, X509) {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", "localhost").unwrap();
+        let name = name.build();
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        let serial = BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap();
+        builder.set_serial_number(&serial).unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        let san = SubjectAlternativeName::new()
+            .dns("localhost")
+            .build(&builder.x509v3_context(None, None))
+            .unwrap();
+        builder.append_extension(san).unwrap();
+        builder
+            .append_extension(BasicConstraints::new().critical().ca().build().unwrap())
+            .unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+        (key, builder.build())
+    }
+
+    /// Serves HTTPS on a local port, answering every request with "ok".
+    /// Returns the port and a count of the requests that arrived.
+    fn serve(key: &PKey<Private>, certificate: &X509) -> (u16, Arc<AtomicUsize>) {
+        let (port, mut requests) = serve_rotating(vec![(key.clone(), certificate.clone())]);
+        (port, requests.remove(0))
+    }
+
+    /// Like `serve`, but each new connection presents the next of
+    /// `identities` in turn. Requests are counted per identity.
+    fn serve_rotating(identities: Vec<(PKey<Private>, X509)>) -> (u16, Vec<Arc<AtomicUsize>>) {
+        let acceptors: Vec<_> = identities
+            .iter()
+            .map(|(key, certificate)| {
+                let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+                acceptor.set_private_key(key).unwrap();
+                acceptor.set_certificate(certificate).unwrap();
+                acceptor.build()
+            })
+            .collect();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests: Vec<_> = identities
+            .iter()
+            .map(|_| Arc::new(AtomicUsize::new(0)))
+            .collect();
+        let counters = requests.clone();
+        std::thread::spawn(move || {
+            for (index, stream) in listener.incoming().flatten().enumerate() {
+                let index = index % acceptors.len();
+                let Ok(mut stream) = acceptors[index].accept(stream) else {
+                    continue;
+                };
+                let mut head = Vec::new();
+                let mut buffer = [0u8; 1024];
+                let complete = |head: &[u8]| head.windows(4).any(|end| end == b"\r\n\r\n");
+                while !complete(&head) {
+                    match stream.read(&mut buffer) {
+                        Ok(read) if read > 0 => head.extend_from_slice(&buffer[..read]),
+                        _ => break,
+                    }
+                }
+                if complete(&head) {
+                    counters[index].fetch_add(1, Ordering::SeqCst);
+                    let _ = stream.write_all(
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+                    );
+                }
+            }
+        });
+        (port, requests)
+    }
+
+    fn spec(port: u16, tls: TlsOptions) -> RequestSpec {
+        let resolve = ResolveOverride::parse(&format!("localhost:{}:127.0.0.1", port)).unwrap();
+        RequestSpec::new(format!("https://localhost:{}/", port))
+            .with_resolve([resolve])
+            .with_tls(tls)
+    }
+
+    fn pin_for(certificate: &X509) -> PublicKeyPin {
+        let key = certificate
+            .public_key()
+            .unwrap()
+            .public_key_to_der()
+            .unwrap();
+        PublicKeyPin::parse(&format!("sha256//{}", STANDARD.encode(Sha256::digest(key)))).unwrap()
+    }
+
+    #[test]
+    fn extracts_subject_public_key_info() {
+        let (_, certificate) = self_signed();
+        let der = certificate.to_der().unwrap();
+        let expected = certificate
+            .public_key()
+            .unwrap()
+            .public_key_to_der()
+            .unwrap();
+        assert_eq!(subject_public_key_info(&der), Some(expected.as_slice()));
+        assert_eq!(subject_public_key_info(&der[..der.len() / 2]), None);
+        assert_eq!(subject_public_key_info(b"not der"), None);
+    }
+
+    #[test]
+    fn parses_pins() {
+        let pin = PublicKeyPin::parse(&format!(
+            "sha256//{};sha256//{}",
+            STANDARD.encode([1u8; 32]),
+            STANDARD.encode([2u8; 32])
+        ))
+        .unwrap();
+        assert_eq!(pin.hashes, vec![[1u8; 32], [2u8; 32]]);
+        assert!(PublicKeyPin::parse("sha256//short").is_err());
+        assert!(PublicKeyPin::parse("sha256//AAAA;md5//AAAA").is_err());
+
+        let (_, certificate) = self_signed();
+        let path = std::env::temp_dir().join(format!("curl-pin-{}.pem", std::process::id()));
+        let pem = certificate
+            .public_key()
+            .unwrap()
+            .public_key_to_pem()
+            .unwrap();
+        std::fs::write(&path, pem).unwrap();
+        let from_file = PublicKeyPin::parse(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(from_file.unwrap(), pin_for(&certificate));
+    }
+
+    #[test]
+    fn mismatched_pin_stops_before_the_request_is_sent() {
+        let (key, certificate) = self_signed();
+        let (port, requests) = serve(&key, &certificate);
+        let tls = TlsOptions {
+            insecure: true,
+            pinned_public_key: Some(PublicKeyPin {
+                hashes: vec![[0u8; 32]],
+            }),
+            ..TlsOptions::default()
+        };
+        let result = execute(&spec(port, tls).with_body(crate::Body::Form("secret".into())));
+        assert!(matches!(result, Err(Error::PinnedPublicKey(_))));
+        assert_eq!(requests.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn matching_pin_sends_the_request() {
+        let (key, certificate) = self_signed();
+        let (port, requests) = serve(&key, &certificate);
+        let tls = TlsOptions {
+            insecure: true,
+            pinned_public_key: Some(pin_for(&certificate)),
+            ..TlsOptions::default()
+        };
+        let outcome = execute(&spec(port, tls)).unwrap();
+        assert_eq!(outcome.text().unwrap(), "ok");
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn pin_is_checked_on_the_connection_that_carries_the_request() {
+        // The first connection shows the pinned key, the next another one.
+        let (pinned_key, pinned) = self_signed();
+        let (other_key, other) = self_signed();
+        let (port, requests) =
+            serve_rotating(vec![(pinned_key, pinned.clone()), (other_key, other)]);
+        let tls = TlsOptions {
+            insecure: true,
+            pinned_public_key: Some(pin_for(&pinned)),
+            ..TlsOptions::default()
+        };
+        let spec = spec(port, tls).with_body(crate::Body::Form("secret".into()));
+        assert_eq!(execute(&spec).unwrap().text().unwrap(), "ok");
+        assert!(matches!(execute(&spec), Err(Error::PinnedPublicKey(_))));
+        assert_eq!(requests[0].load(Ordering::SeqCst), 1);
+        assert_eq!(requests[1].load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn pin_still_verifies_the_server_without_insecure() {
+        let (key, certificate) = self_signed();
+        let (port, requests) = serve(&key, &certificate);
+        let pinned = |ca_cert: Option<PathBuf>| TlsOptions {
+            ca_cert,
+            pinned_public_key: Some(pin_for(&certificate)),
+            ..TlsOptions::default()
+        };
+        assert!(matches!(
+            execute(&spec(port, pinned(None))),
+            Err(Error::PeerVerification(_))
+        ));
+        let path = std::env::temp_dir().join(format!("curl-pin-ca-{}.pem", std::process::id()));
+        std::fs::write(&path, certificate.to_pem().unwrap()).unwrap();
+        let result = execute(&spec(port, pinned(Some(path.clone()))));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(result.unwrap().text().unwrap(), "ok");
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn untrusted_certificate_needs_insecure() {
+        let (key, certificate) = self_signed();
+        let (port, requests) = serve(&key, &certificate);
+        let result = execute(&spec(port, TlsOptions::default()));
+        assert!(matches!(result, Err(Error::PeerVerification(_))));
+        let insecure = TlsOptions {
+            insecure: true,
+            ..TlsOptions::default()
+        };
+        assert_eq!(
+            execute(&spec(port, insecure)).unwrap().text().unwrap(),
+            "ok"
+        );
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn ca_cert_trusts_the_server() {
+        let (key, certificate) = self_signed();
+        let (port, _) = serve(&key, &certificate);
+        let path = std::env::temp_dir().join(format!("curl-ca-{}.pem", std::process::id()));
+        std::fs::write(&path, certificate.to_pem().unwrap()).unwrap();
+        let tls = TlsOptions {
+            ca_cert: Some(path.clone()),
+            ..TlsOptions::default()
+        };
+        let result = execute(&spec(port, tls));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(result.unwrap().text().unwrap(), "ok");
+
+        let missing = TlsOptions {
+            ca_cert: Some(path),
+            ..TlsOptions::default()
+        };
+        assert!(matches!(
+            execute(&spec(port, missing)),
+            Err(Error::CaCertificate(_))
+        ));
+    }
+}
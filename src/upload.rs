@@ -0,0 +1,131 @@
+use std::fs::{File, OpenOptions};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::data::percent_encode;
+use crate::error::Error;
+use crate::request::{Body, RequestSpec};
+
+/// The URL `-T` sends to: when `url` ends with `/`, the file name of `path`
+/// is appended to it.
+pub fn upload_url(url: &str, path: &Path) -> String {
+    if !url.ends_with('/') || path == Path::new("-") {
+        return url.to_string();
+    }
+    match path.file_name() {
+        Some(name) => format!("{}{}", url, percent_encode(&name.to_string_lossy())),
+        None => url.to_string(),
+    }
+}
+
+/// The size of the file `-T` will send, or `None` for stdin.
+pub fn upload_size(path: &Path) -> Result<Option<u64>, Error> {
+    if path == Path::new("-") {
+        return Ok(None);
+    }
+    let metadata = std::fs::metadata(path).map_err(|e| read_error(path, e))?;
+    Ok(Some(metadata.len()))
+}
+
+/// Opens the upload as a streaming body. Files are reopened on every call
+/// so the body can be sent again; stdin is streamed with chunked encoding.
+/// Every byte sent is added to `uploaded`.
+pub(crate) fn upload_body(
+    path: &Path,
+    uploaded: Arc<AtomicU64>,
+) -> Result<reqwest::blocking::Body, Error> {
+    if path == Path::new("-") {
+        return Ok(reqwest::blocking::Body::new(Counted {
+            inner: std::io::stdin(),
+            count: uploaded,
+        }));
+    }
+    let file = File::open(path).map_err(|e| read_error(path, e))?;
+    let len = file.metadata().map_err(|e| read_error(path, e))?.len();
+    Ok(reqwest::blocking::Body::sized(
+        Counted {
+            inner: file,
+            count: uploaded,
+        },
+        len,
+    ))
+}
+
+/// Counts the bytes read through it, so `--speed-limit` can see how an
+/// upload is progressing.
+struct Counted<R> {
+    inner: R,
+    count: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buffer)?;
+        self.count.fetch_add(read as u64, Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+fn read_error(path: &Path, e: std::io::Error) -> Error {
+    Error::BadInput(format!("Failed to read {}: {}", path.display(), e))
+}
+
+/// Stdin copied to a temporary file so an upload from it can be sent more
+/// than once. The file is removed when this is dropped.
+pub(crate) struct SpooledStdin {
+    path: PathBuf,
+}
+
+impl SpooledStdin {
+    /// The file is created afresh, readable only by the current user on
+    /// Unix, and never replaces or follows whatever is already at its path.
+    pub(crate) fn new() -> Result<Self, Error> {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos())
+            .unwrap_or_default();
+        let path =
+            std::env::temp_dir().join(format!("curl-upload-{}-{}", std::process::id(), nanos));
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options
+            .open(&path)
+            .map_err(|e| Error::Write(format!("Failed to create {}: {}", path.display(), e)))?;
+        // Only a file this created is removed again on drop.
+        let spooled = SpooledStdin { path };
+        std::io::copy(&mut std::io::stdin(), &mut file)
+            .map_err(|e| read_error(Path::new("-"), e))?;
+        Ok(spooled)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// When `spec` uploads stdin, spools it and returns a copy of `spec` that
+/// uploads the spooled file instead, so the body can be sent more than once.
+pub(crate) fn spool_stdin(
+    spec: &RequestSpec,
+) -> Result<Option<(SpooledStdin, RequestSpec)>, Error> {
+    match spec.body() {
+        Some(Body::Upload(path)) if path == Path::new("-") => {
+            let spooled = SpooledStdin::new()?;
+            let spec = spec
+                .clone()
+                .with_body(Body::Upload(spooled.path().to_path_buf()));
+            Ok(Some((spooled, spec)))
+        }
+        _ => Ok(None),
+    }
+}
+
+impl Drop for SpooledStdin {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,85 @@
+//! `-v` tracing of the exchange, written to stderr so it never mixes with
+//! the body on stdout.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use reqwest::blocking::{Request, Response};
+use reqwest::header::{AUTHORIZATION, HOST, PROXY_AUTHORIZATION};
+use reqwest::tls::TlsInfo;
+use sha2::{Digest, Sha256};
+
+use crate::tls;
+
+pub(crate) fn resolved(host: &str, addrs: &[SocketAddr]) {
+    for addr in addrs {
+        eprintln!("* Resolved {} to {}", host, addr);
+    }
+}
+
+pub(crate) fn request(request: &Request) {
+    let url = request.url();
+    let path = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    eprintln!("> {} {} {:?}", request.method(), path, request.version());
+    if let (Some(host), false) = (url.host_str(), request.headers().contains_key(HOST)) {
+        match url.port() {
+            Some(port) => eprintln!("> host: {}:{}", host, port),
+            None => eprintln!("> host: {}", host),
+        }
+    }
+    for (name, value) in request.headers() {
+        if name == AUTHORIZATION || name == PROXY_AUTHORIZATION {
+            let value = String::from_utf8_lossy(value.as_bytes());
+            let scheme = value.split_whitespace().next().unwrap_or_default();
+            eprintln!("> {}: {} [redacted]", name, scheme);
+        } else {
+            eprintln!("> {}: {}", name, String::from_utf8_lossy(value.as_bytes()));
+        }
+    }
+    eprintln!(">");
+    if let Some(body) = request.body().and_then(|body| body.as_bytes()) {
+        eprintln!("}} [{} bytes data]", body.len());
+    }
+}
+
+pub(crate) fn response(response: &Response, elapsed: Duration) {
+    if let Some(addr) = response.remote_addr() {
+        eprintln!("* Connected to {} port {}", addr.ip(), addr.port());
+    }
+    if let Some(certificate) = response
+        .extensions()
+        .get::<TlsInfo>()
+        .and_then(|info| info.peer_certificate())
+    {
+        if let Some((subject, issuer)) = tls::certificate_names(certificate) {
+            eprintln!("* Server certificate:");
+            eprintln!("*  subject: {}", subject);
+            eprintln!("*  issuer: {}", issuer);
+        }
+        eprintln!(
+            "* Server certificate SHA-256 fingerprint: {}",
+            fingerprint(certificate)
+        );
+        // Only the certificate is reported back from the connection that
+        // carried the request; the TLS backend keeps the rest to itself.
+        eprintln!("* TLS version and cipher: not reported by the TLS backend");
+    }
+    eprintln!("< {:?} {}", response.version(), response.status());
+    for (name, value) in response.headers() {
+        eprintln!("< {}: {}", name, String::from_utf8_lossy(value.as_bytes()));
+    }
+    eprintln!("<");
+    eprintln!("* Response head received after {} ms", elapsed.as_millis());
+}
+
+/// Colon-separated uppercase hex of the SHA-256 digest of `der`.
+pub(crate) fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}